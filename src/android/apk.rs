@@ -4,13 +4,21 @@ use apk_rs::axml::XmlElementStream;
 use http::Uri;
 use apk_rs::axml::XmlEvent;
 use regex::Regex;
-use apk_rs::resources::resources::is_package_reference;
 use apk_rs::typedvalue::TypedValue;
 use apk_rs::axml::ElementStart;
-use apk_rs::resources::resources::Resources;
 use std::str::FromStr;
-use std::collections::HashMap;
-use crate::android::assetlinks::Assetlink;
+use std::collections::{HashMap, HashSet};
+use crate::android::assetlinks::{self, Assetlink};
+use crate::android::bundle;
+use crate::android::dex;
+use crate::android::inventory::DeepLink;
+use crate::android::resources::{self, ResourceTable};
+use crate::android::signing;
+use crate::android::source;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Authority {
@@ -19,16 +27,81 @@ pub struct Authority {
 }
 
 impl Authority {
+    // a host starting with '*' matches every host ending with the rest of it, like IntentFilter.AuthorityEntry
+    pub fn is_wildcard(&self) -> bool {
+        self.host.starts_with('*')
+    }
+
+    // the host whose assetlinks.json the verifier fetches, for *.example.com that is example.com.
+    // None for a single '*' which cannot be verified.
+    pub fn verification_host(&self) -> Option<&str> {
+        if !self.is_wildcard() {
+            return Some(&self.host);
+        }
+        let base = self.host[1..].trim_start_matches('.');
+        if base.is_empty() {
+            None
+        } else {
+            Some(base)
+        }
+    }
+
+    // like IntentFilter.AuthorityEntry, a host without port accepts any port
     fn matches(&self, host: &str, port: Option<u16>) -> bool {
-        self.host == host.to_string() && self.port == port
+        if self.port.is_some() && self.port != port {
+            return false;
+        }
+        if self.is_wildcard() {
+            let suffix = &self.host[1..];
+            host.len() >= suffix.len() && host[host.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+        } else {
+            self.host.eq_ignore_ascii_case(host)
+        }
     }
 }
 
-#[derive(Debug, Clone)]
-enum PathMatcher {
+#[test]
+fn test_authority_matches() {
+    let wildcard = Authority { host: "*.example.com".to_string(), port: None };
+    assert!(wildcard.matches("www.example.com", None));
+    assert!(wildcard.matches("a.b.Example.com", None));
+    assert!(!wildcard.matches("example.com", None));
+    assert!(!wildcard.matches("www.example.org", None));
+    assert!(wildcard.matches("www.example.com", Some(8443)));
+    assert_eq!(Some("example.com"), wildcard.verification_host());
+
+    let any = Authority { host: "*".to_string(), port: None };
+    assert!(any.matches("example.org", None));
+    assert_eq!(None, any.verification_host());
+
+    let exact = Authority { host: "example.com".to_string(), port: Some(8080) };
+    assert!(exact.matches("EXAMPLE.com", Some(8080)));
+    assert!(!exact.matches("www.example.com", Some(8080)));
+    assert!(!exact.matches("example.com", Some(443)));
+    assert!(!exact.matches("example.com", None));
+    assert_eq!(Some("example.com"), exact.verification_host());
+
+    let url = Uri::from_str("https://example.com:443/x").unwrap();
+    let without_port = Authority { host: "example.com".to_string(), port: None };
+    assert!(without_port.matches(url.host().unwrap(), url.port_part().map(|p| p.as_u16())));
+}
+
+impl fmt::Display for Authority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.port {
+            Some(port) => write!(f, "{}:{}", self.host, port),
+            None => write!(f, "{}", self.host),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathMatcher {
     Literal(String),
     Prefix(String),
     Pattern(String),
+    AdvancedPattern(String),
+    Suffix(String),
 }
 
 impl PathMatcher {
@@ -37,10 +110,125 @@ impl PathMatcher {
             PathMatcher::Literal(p) => p == path,
             PathMatcher::Prefix(pre) => path.starts_with(pre),
             PathMatcher::Pattern(pattern) => match_pattern(path, pattern),
+            PathMatcher::AdvancedPattern(pattern) => match parse_advanced_pattern(pattern) {
+                Ok(tokens) => match_advanced_pattern(path, &tokens),
+                Err(_) => false,
+            },
+            PathMatcher::Suffix(suffix) => path.ends_with(suffix),
         }
     }
 }
 
+impl PathMatcher {
+    // the suffix of the manifest attribute (e.g. pathPrefix, queryPrefix) and the pattern
+    fn attribute_suffix(&self) -> (&'static str, &str) {
+        match self {
+            PathMatcher::Literal(p) => ("", p),
+            PathMatcher::Prefix(p) => ("Prefix", p),
+            PathMatcher::Pattern(p) => ("Pattern", p),
+            PathMatcher::AdvancedPattern(p) => ("AdvancedPattern", p),
+            PathMatcher::Suffix(p) => ("Suffix", p),
+        }
+    }
+
+    // reads the path, query or fragment attributes of a <data> element, with all configuration variants
+    fn from_data_element(element: &Element, attribute: &str) -> Vec<PathMatcher> {
+        let mut res = Vec::new();
+        for p in element.string_attribute_variants(attribute) {
+            res.push(PathMatcher::Literal(p));
+        }
+        for p in element.string_attribute_variants(&format!("{}Prefix", attribute)) {
+            res.push(PathMatcher::Prefix(p));
+        }
+        for p in element.string_attribute_variants(&format!("{}Pattern", attribute)) {
+            res.push(PathMatcher::Pattern(p));
+        }
+        for p in element.string_attribute_variants(&format!("{}AdvancedPattern", attribute)) {
+            res.push(PathMatcher::AdvancedPattern(p));
+        }
+        for p in element.string_attribute_variants(&format!("{}Suffix", attribute)) {
+            res.push(PathMatcher::Suffix(p));
+        }
+        res
+    }
+}
+
+impl fmt::Display for PathMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (suffix, pattern) = self.attribute_suffix();
+        write!(f, "path{}=\"{}\"", suffix, pattern)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UriPart {
+    Path,
+    Query,
+    Fragment,
+}
+
+// a <data> element inside of a <uri-relative-filter-group> (Android 15)
+#[derive(Debug, Clone)]
+pub struct UriRelativeFilter {
+    part: UriPart,
+    matcher: PathMatcher,
+}
+
+impl UriRelativeFilter {
+    // follows UriRelativeFilter.matchData: a query filter matches if any of the parameters matches
+    fn matches(&self, url: &Uri, fragment: Option<&str>) -> bool {
+        match self.part {
+            UriPart::Path => self.matcher.matches(url.path()),
+            UriPart::Query => match url.query() {
+                Some(query) => {
+                    let mut params: Vec<&str> = query.split('&').collect();
+                    if params.len() == 1 {
+                        params = query.split(';').collect();
+                    }
+                    params.iter().any(|p| self.matcher.matches(p))
+                }
+                None => false,
+            },
+            UriPart::Fragment => match fragment {
+                Some(fragment) => self.matcher.matches(fragment),
+                None => false,
+            },
+        }
+    }
+}
+
+impl fmt::Display for UriRelativeFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let part = match self.part {
+            UriPart::Path => "path",
+            UriPart::Query => "query",
+            UriPart::Fragment => "fragment",
+        };
+        let (suffix, pattern) = self.matcher.attribute_suffix();
+        write!(f, "{}{}=\"{}\"", part, suffix, pattern)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UriRelativeFilterGroup {
+    allow: bool,
+    filters: Vec<UriRelativeFilter>,
+}
+
+impl UriRelativeFilterGroup {
+    // all filters of a group have to match
+    fn matches(&self, url: &Uri, fragment: Option<&str>) -> bool {
+        self.filters.iter().all(|f| f.matches(url, fragment))
+    }
+}
+
+impl fmt::Display for UriRelativeFilterGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let filters: Vec<String> = self.filters.iter().map(|f| f.to_string()).collect();
+        write!(f, "uri-relative-filter-group allow=\"{}\" [{}]", self.allow, filters.join(", "))
+    }
+}
+
 // algorithm from: https://github.com/aosp-mirror/platform_frameworks_base/blob/6bebb8418ceecf44d2af40033870f3aabacfe36e/core/java/android/os/PatternMatcher.java
 fn match_pattern(path: &str, pattern: &str) -> bool {
     if pattern.len() == 0 {
@@ -115,7 +303,7 @@ fn match_pattern(path: &str, pattern: &str) -> bool {
     if ip >= np && im >= nm {
         return true;
     }
-    if ip == np - 2 && pattern[ip] == '.' && pattern[ip + 1] == '*' {
+    if np >= 2 && ip == np - 2 && pattern[ip] == '.' && pattern[ip + 1] == '*' {
         return true;
     }
 
@@ -131,32 +319,318 @@ fn test_match_pattern() {
     assert!(match_pattern("/foo", "/.*"));
     assert!(!match_pattern("/foo/bar/baz", "/.*/bar")); // this is what android does...
     assert!(!match_pattern("/foobarbaz", "/f.*baz")); // counter intuitive, but this is what android does...
+    assert!(!match_pattern("/ab", "/a"));
+    assert!(match_pattern("/a", "/a.*"));
+    assert!(match_pattern("/aaa", "/a*"));
+    assert!(match_pattern("/.", "/\\."));
+    assert!(match_pattern("/a", "/\\.")); // an escaped dot still matches any character in android
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum CharMatcher {
+    Literal(char),
+    Any,
+    Set(Vec<(char, char)>),
+    InverseSet(Vec<(char, char)>),
+}
+
+impl CharMatcher {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            CharMatcher::Literal(l) => *l == c,
+            CharMatcher::Any => true,
+            CharMatcher::Set(ranges) => ranges.iter().any(|r| c >= r.0 && c <= r.1),
+            CharMatcher::InverseSet(ranges) => !ranges.iter().any(|r| c >= r.0 && c <= r.1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AdvancedPatternToken {
+    matcher: CharMatcher,
+    min: usize,
+    max: usize,
+    has_modifier: bool,
+}
+
+// port of PatternMatcher.parseAndVerifyAdvancedPattern. the error messages are the ones android
+// uses when it rejects the manifest.
+fn parse_advanced_pattern(pattern: &str) -> Result<Vec<AdvancedPatternToken>, String> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let np = pattern.len();
+    let mut tokens: Vec<AdvancedPatternToken> = Vec::new();
+    let mut ip = 0;
+
+    fn unescape(pattern: &[char], ip: &mut usize) -> Result<char, String> {
+        if pattern[*ip] == '\\' {
+            *ip += 1;
+            if *ip >= pattern.len() {
+                return Err("Escape found at end of pattern!".to_string());
+            }
+        }
+        Ok(pattern[*ip])
+    }
+
+    while ip < np {
+        let matcher = match pattern[ip] {
+            '[' => {
+                let inverse = ip + 1 < np && pattern[ip + 1] == '^';
+                ip += if inverse { 2 } else { 1 };
+                let mut ranges = Vec::new();
+                loop {
+                    if ip >= np {
+                        return Err("Set was not terminated!".to_string());
+                    }
+                    if pattern[ip] == ']' {
+                        if ranges.is_empty() {
+                            return Err("You must define characters in a set.".to_string());
+                        }
+                        break;
+                    }
+                    let low = unescape(&pattern, &mut ip)?;
+                    if ip + 2 < np && pattern[ip + 1] == '-' && pattern[ip + 2] != ']' {
+                        ip += 2;
+                        let high = unescape(&pattern, &mut ip)?;
+                        ranges.push((low, high));
+                    } else {
+                        ranges.push((low, low));
+                    }
+                    ip += 1;
+                }
+                if inverse {
+                    CharMatcher::InverseSet(ranges)
+                } else {
+                    CharMatcher::Set(ranges)
+                }
+            }
+            '*' | '+' | '{' => {
+                let token = match tokens.last_mut() {
+                    Some(token) if !token.has_modifier => token,
+                    _ => return Err("Modifier must follow a token.".to_string()),
+                };
+                token.has_modifier = true;
+                match pattern[ip] {
+                    '*' => {
+                        token.min = 0;
+                        token.max = usize::max_value();
+                    }
+                    '+' => {
+                        token.min = 1;
+                        token.max = usize::max_value();
+                    }
+                    _ => {
+                        let end = match pattern[ip..].iter().position(|&c| c == '}') {
+                            Some(end) => ip + end,
+                            None => return Err("Range not ended with '}'".to_string()),
+                        };
+                        let range: String = pattern[ip + 1..end].iter().collect();
+                        let parse = |s: &str| {
+                            s.parse::<usize>()
+                                .map_err(|_| "Range number format incorrect".to_string())
+                        };
+                        let (min, max) = match range.find(',') {
+                            None => (parse(&range)?, parse(&range)?),
+                            Some(comma) if comma == range.len() - 1 => {
+                                (parse(&range[..comma])?, usize::max_value())
+                            }
+                            Some(comma) => (parse(&range[..comma])?, parse(&range[comma + 1..])?),
+                        };
+                        if min > max {
+                            return Err("Range quantifier minimum is greater than maximum".to_string());
+                        }
+                        token.min = min;
+                        token.max = max;
+                        ip = end;
+                    }
+                }
+                ip += 1;
+                continue;
+            }
+            // a closing brace outside of a range is silently dropped by android
+            '}' => {
+                ip += 1;
+                continue;
+            }
+            '.' => CharMatcher::Any,
+            _ => CharMatcher::Literal(unescape(&pattern, &mut ip)?),
+        };
+        tokens.push(AdvancedPatternToken {
+            matcher,
+            min: 1,
+            max: 1,
+            has_modifier: false,
+        });
+        ip += 1;
+    }
+
+    Ok(tokens)
+}
+
+// port of PatternMatcher.matchAdvancedPattern: every token consumes as many characters as possible,
+// there is no backtracking.
+fn match_advanced_pattern(path: &str, tokens: &[AdvancedPatternToken]) -> bool {
+    let path: Vec<char> = path.chars().collect();
+    let mut im = 0;
+    for token in tokens {
+        let mut matched = 0;
+        while matched < token.max && im + matched < path.len() && token.matcher.matches(path[im + matched]) {
+            matched += 1;
+        }
+        if matched < token.min {
+            return false;
+        }
+        im += matched;
+    }
+
+    im >= path.len()
+}
+
+#[test]
+fn test_match_advanced_pattern() {
+    fn matches(path: &str, pattern: &str) -> bool {
+        match_advanced_pattern(path, &parse_advanced_pattern(pattern).unwrap())
+    }
+
+    // cases from the AOSP PatternMatcherTest
+    assert!(matches("", ""));
+    assert!(matches("a", "a"));
+    assert!(!matches("b", "a"));
+    assert!(!matches("aa", "a"));
+    assert!(matches("", "a*"));
+    assert!(matches("aaa", "a*"));
+    assert!(!matches("", "a+"));
+    assert!(matches("aaa", "a+"));
+    assert!(matches("", ".*"));
+    assert!(matches("/anything/at/all", ".*"));
+    assert!(matches("a", "."));
+    assert!(!matches("ab", "."));
+    assert!(matches(".", "\\."));
+    assert!(!matches("a", "\\."));
+    assert!(matches("a*", "a\\*"));
+
+    // ranges
+    assert!(!matches("a", "a{2}"));
+    assert!(matches("aa", "a{2}"));
+    assert!(!matches("aaa", "a{2}"));
+    assert!(matches("a", "a{1,3}"));
+    assert!(matches("aaa", "a{1,3}"));
+    assert!(!matches("aaaa", "a{1,3}"));
+    assert!(!matches("a", "a{2,}"));
+    assert!(matches("aaaaaaaa", "a{2,}"));
+    assert!(matches("abab", "[ab]{4}"));
+
+    // sets
+    assert!(matches("a", "[abc]"));
+    assert!(!matches("d", "[abc]"));
+    assert!(matches("m", "[a-z]"));
+    assert!(!matches("M", "[a-z]"));
+    assert!(matches("M", "[a-zA-Z]"));
+    assert!(matches("5", "[a-z0-9]"));
+    assert!(!matches("m", "[^a-z]"));
+    assert!(matches("5", "[^a-z]"));
+    assert!(matches("-", "[a-]"));
+    assert!(matches("]", "[\\]]"));
+    assert!(matches(".", "[.]"));
+    assert!(!matches("a", "[.]"));
+    assert!(matches("/product/123", "/product/[0-9]+"));
+    assert!(!matches("/product/12a", "/product/[0-9]+"));
+
+    // matching is greedy without backtracking, so a wildcard swallows the rest of the path
+    assert!(!matches("/foo/bar", "/.*/bar"));
+    assert!(matches("/foo/bar", "/[^/]+/bar"));
+
+    // invalid patterns make android reject the manifest
+    assert!(parse_advanced_pattern("*").is_err());
+    assert!(parse_advanced_pattern("a**").is_err());
+    assert!(parse_advanced_pattern("a+{2}").is_err());
+    assert!(parse_advanced_pattern("[]").is_err());
+    assert!(parse_advanced_pattern("[a-z").is_err());
+    assert!(parse_advanced_pattern("a{2").is_err());
+    assert!(parse_advanced_pattern("a{x}").is_err());
+    assert!(parse_advanced_pattern("a{3,2}").is_err());
+    assert!(parse_advanced_pattern("a\\").is_err());
+}
+
+#[test]
+fn test_path_matcher() {
+    assert!(PathMatcher::Suffix(".pdf".to_string()).matches("/docs/file.pdf"));
+    assert!(!PathMatcher::Suffix(".pdf".to_string()).matches("/docs/file.pdf.html"));
+    assert!(PathMatcher::Pattern("/item/.*".to_string()).matches("/item/42"));
+    assert!(!PathMatcher::Pattern("/item/.*".to_string()).matches("/items/42"));
+    assert!(PathMatcher::AdvancedPattern("/item/[0-9]+".to_string()).matches("/item/42"));
+    assert!(!PathMatcher::AdvancedPattern("/item/[0-9+".to_string()).matches("/item/42"));
 }
 
 #[derive(Debug, Clone)]
 pub struct IntentFilter {
+    // for filters of an activity-alias this is the targetActivity
     activity_name: String,
+    alias_name: Option<String>,
+    priority: i32,
     action: Vec<String>,
     category: Vec<String>,
     auto_verify: bool,
     schemes: Vec<String>,
     authorities: Vec<Authority>,
     path_matchers: Vec<PathMatcher>,
+    uri_relative_filter_groups: Vec<UriRelativeFilterGroup>,
+    // the <data> elements as declared, used to tell which combinations were intended
+    data_elements: Vec<DataElement>,
+}
+
+#[derive(Debug, Clone)]
+struct DataElement {
+    scheme: Option<String>,
+    authority: Option<Authority>,
+    path_matchers: Vec<PathMatcher>,
+}
+
+// one kind of URL accepted by an intent filter. android merges all <data> elements of a filter,
+// so every scheme is combined with every host and every host with every path.
+#[derive(Debug, Clone)]
+pub struct DataCombination {
+    pub scheme: String,
+    pub authority: Option<Authority>,
+    pub path_matcher: Option<PathMatcher>,
+    // false if the combination only exists because of the merging
+    pub intended: bool,
+}
+
+impl fmt::Display for DataCombination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.authority {
+            Some(ref authority) => write!(f, "{}://{}", self.scheme, authority)?,
+            None => write!(f, "{}://*", self.scheme)?,
+        }
+        if let Some(ref matcher) = self.path_matcher {
+            write!(f, " {}", matcher)?;
+        }
+        Ok(())
+    }
 }
 
 impl IntentFilter {
     fn new(activity_name: String, auto_verify: bool) -> Self {
         Self {
             activity_name,
+            alias_name: None,
+            priority: 0,
             action: Vec::new(),
             category: Vec::new(),
             auto_verify,
             schemes: Vec::new(),
             authorities: Vec::new(),
             path_matchers: Vec::new(),
+            uri_relative_filter_groups: Vec::new(),
+            data_elements: Vec::new(),
         }
     }
 
+    // any VIEW filter with a scheme, including custom schemes and filters that are not BROWSABLE
+    fn is_deep_link(&self) -> bool {
+        !self.schemes.is_empty() && self.action.contains(&"android.intent.action.VIEW".to_string())
+    }
+
     fn is_relevant(&self) -> bool {
         self.contains_http_scheme()
             && self.action.contains(&"android.intent.action.VIEW".to_string())
@@ -171,37 +645,150 @@ impl IntentFilter {
         self.schemes.iter().filter(|&s| *s != "http".to_string() && *s != "https".to_string()).next().is_some()
     }
 
-    fn matches_url(&self, url: Uri) -> bool {
-        if let Some(scheme) = url.scheme_part() {
-            if !self.schemes.contains(&scheme.to_string()) {
-                return false;
-            }
+    // android ignores hosts without scheme and paths without host
+    pub fn data_combinations(&self) -> Vec<DataCombination> {
+        let mut res = Vec::new();
+        let mut authorities: Vec<Option<&Authority>> = self.authorities.iter().map(Some).collect();
+        if authorities.is_empty() {
+            authorities.push(None);
         }
-
-        let mut auth_matches = false;
-        if let Some(auth) = url.authority_part() {
-            for authority in &self.authorities {
-                if authority.matches(auth.host(), auth.port()) {
-                    auth_matches = true;
-                    break;
+        for scheme in &self.schemes {
+            for authority in &authorities {
+                let mut path_matchers: Vec<Option<&PathMatcher>> = Vec::new();
+                if authority.is_some() {
+                    path_matchers.extend(self.path_matchers.iter().map(Some));
+                }
+                if path_matchers.is_empty() {
+                    path_matchers.push(None);
+                }
+                for path_matcher in path_matchers {
+                    res.push(DataCombination {
+                        scheme: scheme.clone(),
+                        authority: authority.cloned(),
+                        path_matcher: path_matcher.cloned(),
+                        intended: self.is_intended_combination(scheme, *authority, path_matcher),
+                    });
                 }
-            }
-            if !auth_matches {
-                return false;
             }
         }
+        res
+    }
 
-        let mut path_matches = false;
-        for matcher in &self.path_matchers {
-            if matcher.matches(url.path()) {
-                path_matches = true;
-            }
+    // a pair of scheme and host (or host and path) is intended if it was declared in the same <data> element,
+    // or if one of them was declared without the other. a custom scheme is only intended with the hosts
+    // of its own <data> element if the filter also has http(s) schemes.
+    fn is_intended_combination(&self, scheme: &str, authority: Option<&Authority>, path_matcher: Option<&PathMatcher>) -> bool {
+        let authority = match authority {
+            Some(authority) => authority,
+            None => return true,
+        };
+
+        let declared_together = self.data_elements.iter().any(|d| {
+            d.authority.as_ref() == Some(authority) && d.scheme.as_ref().map(|s| s == scheme).unwrap_or(false)
+        });
+        let custom_with_http = scheme != "http" && scheme != "https" && self.contains_http_scheme();
+        let scheme_intended = declared_together
+            || (!custom_with_http
+                && self.data_elements.iter().any(|d| d.authority.as_ref() == Some(authority) && d.scheme.is_none()));
+        let path_intended = match path_matcher {
+            Some(path_matcher) => self.data_elements.iter().any(|d| {
+                d.path_matchers.contains(path_matcher) && d.authority.as_ref().map(|a| a == authority).unwrap_or(true)
+            }),
+            None => true,
+        };
+
+        scheme_intended && path_intended
+    }
+
+    fn matches_url(&self, url: Uri) -> bool {
+        self.match_url(&url, None).is_some()
+    }
+
+    // follows IntentFilter.matchData: a filter without hosts accepts any host, a filter without paths any path.
+    // with paths or uri relative filter groups a URL has to match a path or be allowed by the first matching group.
+    // http::Uri drops the fragment, so it has to be passed separately.
+    fn match_url(&self, url: &Uri, fragment: Option<&str>) -> Option<UrlMatch> {
+        let scheme = url.scheme_part()?.as_str();
+        if !self.schemes.iter().any(|s| s == scheme) {
+            return None;
         }
-        if !path_matches {
-            return false;
+
+        let authority = if self.authorities.is_empty() {
+            None
+        } else {
+            let auth = url.authority_part()?;
+            let authority = self.authorities
+                .iter()
+                .find(|a| a.matches(auth.host(), auth.port()))?;
+            Some(authority.clone())
+        };
+
+        let mut path_matcher = None;
+        let mut filter_group = None;
+        if authority.is_some() && !(self.path_matchers.is_empty() && self.uri_relative_filter_groups.is_empty()) {
+            path_matcher = self.path_matchers.iter().find(|m| m.matches(url.path())).cloned();
+            if path_matcher.is_none() {
+                // the first group matching the URL decides whether it is accepted
+                let group = self.uri_relative_filter_groups
+                    .iter()
+                    .find(|g| g.matches(url, fragment))?;
+                if !group.allow {
+                    return None;
+                }
+                filter_group = Some(group.clone());
+            }
         }
 
-        true
+        Some(UrlMatch {
+            activity_name: self.activity_name.clone(),
+            alias_name: self.alias_name.clone(),
+            priority: self.priority,
+            scheme: scheme.to_string(),
+            authority,
+            path_matcher,
+            filter_group,
+        })
+    }
+}
+
+// an intent filter accepting a URL, together with the data rules that matched
+#[derive(Debug)]
+pub struct UrlMatch {
+    pub activity_name: String,
+    pub alias_name: Option<String>,
+    pub priority: i32,
+    pub scheme: String,
+    pub authority: Option<Authority>,
+    pub path_matcher: Option<PathMatcher>,
+    pub filter_group: Option<UriRelativeFilterGroup>,
+}
+
+impl UrlMatch {
+    pub fn component_name(&self) -> &str {
+        self.alias_name.as_ref().unwrap_or(&self.activity_name)
+    }
+
+    pub fn to_string_human(&self) -> String {
+        let authority = match self.authority {
+            Some(ref authority) => format!("host '{}'", authority),
+            None => "any host".to_string(),
+        };
+        let path = match self.path_matcher {
+            Some(ref matcher) => format!("path rule {}", matcher),
+            None => "any path".to_string(),
+        };
+        let group = match self.filter_group {
+            Some(ref group) => format!(", {}", group),
+            None => "".to_string(),
+        };
+        let alias = match self.alias_name {
+            Some(ref alias) => format!(" through alias '{}'", alias),
+            None => "".to_string(),
+        };
+        format!(
+            "Activity '{}'{} handles the URL (scheme '{}', {}, {}{}).",
+            self.activity_name, alias, self.scheme, authority, path, group
+        )
     }
 }
 
@@ -209,37 +796,371 @@ impl IntentFilter {
 fn test_intent_filter_matching() {
     let filter = IntentFilter {
         activity_name: "foo".to_string(),
+        alias_name: None,
+        priority: 0,
         auto_verify: true,
         action: vec!["android.intent.action.VIEW".to_string()],
         category: vec!["android.intent.category.BROWSABLE".to_string()],
         schemes: vec!["http".to_string(), "https".to_string()],
         authorities: vec![Authority { host: "example.com".to_string(), port: None }],
         path_matchers: vec![PathMatcher::Literal("/bar".to_string()), PathMatcher::Literal("/baz".to_string())],
+        uri_relative_filter_groups: Vec::new(),
+        data_elements: Vec::new(),
     };
     assert!(filter.is_relevant());
     assert!(filter.matches_url(Uri::from_str("http://example.com/bar").unwrap()));
     assert!(!filter.matches_url(Uri::from_str("http://exemple.com/bar").unwrap()));
-    assert!(!filter.matches_url(Uri::from_str("http://example.com:8080/bar").unwrap()));
+    assert!(filter.matches_url(Uri::from_str("http://example.com:8080/bar").unwrap()));
+
+    let m = filter.match_url(&Uri::from_str("https://example.com/baz").unwrap(), None).unwrap();
+    assert_eq!("https", m.scheme);
+    match m.path_matcher {
+        Some(PathMatcher::Literal(ref p)) => assert_eq!("/baz", p),
+        _ => panic!("expected literal path matcher"),
+    }
+    assert!(filter.match_url(&Uri::from_str("ftp://example.com/baz").unwrap(), None).is_none());
+
+    let any_path = IntentFilter {
+        path_matchers: Vec::new(),
+        ..filter
+    };
+    let m = any_path.match_url(&Uri::from_str("https://example.com/anything").unwrap(), None).unwrap();
+    assert!(m.path_matcher.is_none());
+}
+
+#[test]
+fn test_data_combinations() {
+    let data = |scheme: Option<&str>, host: Option<&str>, path: Option<&str>| DataElement {
+        scheme: scheme.map(|s| s.to_string()),
+        authority: host.map(|h| Authority { host: h.to_string(), port: None }),
+        path_matchers: path.map(|p| PathMatcher::Prefix(p.to_string())).into_iter().collect(),
+    };
+    let mut filter = IntentFilter::new("foo".to_string(), true);
+    filter.data_elements = vec![
+        data(Some("https"), Some("example.com"), Some("/shop")),
+        data(None, Some("blog.example.com"), Some("/posts")),
+        data(Some("myapp"), None, None),
+    ];
+    filter.schemes = vec!["https".to_string(), "myapp".to_string()];
+    filter.authorities = filter.data_elements.iter().filter_map(|d| d.authority.clone()).collect();
+    filter.path_matchers = filter.data_elements.iter().flat_map(|d| d.path_matchers.clone()).collect();
+
+    let combinations: Vec<String> = filter.data_combinations()
+        .iter()
+        .map(|c| format!("{}{}", c, if c.intended { "" } else { " (unintended)" }))
+        .collect();
+    assert_eq!(vec![
+        "https://example.com pathPrefix=\"/shop\"",
+        "https://example.com pathPrefix=\"/posts\" (unintended)",
+        "https://blog.example.com pathPrefix=\"/shop\" (unintended)",
+        "https://blog.example.com pathPrefix=\"/posts\"",
+        "myapp://example.com pathPrefix=\"/shop\" (unintended)",
+        "myapp://example.com pathPrefix=\"/posts\" (unintended)",
+        "myapp://blog.example.com pathPrefix=\"/shop\" (unintended)",
+        "myapp://blog.example.com pathPrefix=\"/posts\" (unintended)",
+    ], combinations);
+
+    // without hosts paths are ignored
+    let mut filter = IntentFilter::new("foo".to_string(), false);
+    filter.schemes = vec!["myapp".to_string()];
+    filter.path_matchers = vec![PathMatcher::Prefix("/a".to_string())];
+    let combinations = filter.data_combinations();
+    assert_eq!(1, combinations.len());
+    assert_eq!("myapp://*", combinations[0].to_string());
+    assert!(combinations[0].intended);
+
+    // a custom scheme declared with a host in the same element is intended, even next to https
+    let mut filter = IntentFilter::new("foo".to_string(), true);
+    filter.data_elements = vec![
+        data(Some("myapp"), Some("example.com"), None),
+        data(Some("https"), Some("example.com"), None),
+    ];
+    filter.schemes = vec!["myapp".to_string(), "https".to_string()];
+    filter.authorities = vec![Authority { host: "example.com".to_string(), port: None }];
+    assert!(filter.data_combinations().iter().all(|c| c.intended));
+}
+
+#[test]
+fn test_uri_relative_filter_groups() {
+    let group = |allow: bool, filters: Vec<(UriPart, PathMatcher)>| UriRelativeFilterGroup {
+        allow,
+        filters: filters
+            .into_iter()
+            .map(|(part, matcher)| UriRelativeFilter { part, matcher })
+            .collect(),
+    };
+    let mut filter = IntentFilter::new("foo".to_string(), true);
+    filter.schemes.push("https".to_string());
+    filter.authorities.push(Authority { host: "example.com".to_string(), port: None });
+    filter.uri_relative_filter_groups = vec![
+        group(false, vec![(UriPart::Query, PathMatcher::Prefix("utm_".to_string()))]),
+        group(true, vec![
+            (UriPart::Path, PathMatcher::Prefix("/product/".to_string())),
+            (UriPart::Fragment, PathMatcher::Literal("reviews".to_string())),
+        ]),
+        group(true, vec![(UriPart::Path, PathMatcher::Prefix("/".to_string()))]),
+    ];
+
+    let url = |s: &str| Uri::from_str(s).unwrap();
+    assert!(filter.match_url(&url("https://example.com/a"), None).is_some());
+    assert!(filter.match_url(&url("https://example.com/a?utm_source=mail"), None).is_none());
+    assert!(filter.match_url(&url("https://example.com/a?id=1&utm_source=mail"), None).is_none());
+    assert!(filter.match_url(&url("https://example.com/a?id=1;utm_source=mail"), None).is_none());
+
+    let m = filter.match_url(&url("https://example.com/product/1"), Some("reviews")).unwrap();
+    assert_eq!(
+        "uri-relative-filter-group allow=\"true\" [pathPrefix=\"/product/\", fragment=\"reviews\"]",
+        m.filter_group.unwrap().to_string()
+    );
+    let m = filter.match_url(&url("https://example.com/product/1"), None).unwrap();
+    assert_eq!(1, m.filter_group.unwrap().filters.len());
+
+    // with groups present a URL matching none of them is rejected
+    filter.uri_relative_filter_groups.pop();
+    assert!(filter.match_url(&url("https://example.com/a"), None).is_none());
+
+    // a path or a group is enough
+    filter.path_matchers.push(PathMatcher::Literal("/a".to_string()));
+    let m = filter.match_url(&url("https://example.com/a?utm_source=mail"), None).unwrap();
+    assert!(m.path_matcher.is_some() && m.filter_group.is_none());
+    let m = filter.match_url(&url("https://example.com/product/1"), Some("reviews")).unwrap();
+    assert!(m.path_matcher.is_none() && m.filter_group.is_some());
+    assert!(filter.match_url(&url("https://example.com/b"), None).is_none());
+}
+
+// class names in the manifest can be relative to the package
+fn qualified_class_name(package: Option<&str>, name: &str) -> String {
+    match package {
+        Some(package) if name.starts_with('.') => format!("{}{}", package, name),
+        Some(package) if !name.contains('.') => format!("{}.{}", package, name),
+        _ => name.to_string(),
+    }
+}
+
+#[test]
+fn test_qualified_class_name() {
+    assert_eq!("com.example.Main", qualified_class_name(Some("com.example"), ".Main"));
+    assert_eq!("com.example.Main", qualified_class_name(Some("com.example"), "Main"));
+    assert_eq!("org.other.Main", qualified_class_name(Some("com.example"), "org.other.Main"));
+    assert_eq!(".Main", qualified_class_name(None, ".Main"));
+}
+
+// an <activity> or <activity-alias>
+#[derive(Debug, Clone)]
+pub struct Activity {
+    name: String,
+    target_activity: Option<String>,
+    exported: Option<bool>,
+    enabled: bool,
+    has_intent_filter: bool,
+}
+
+impl Activity {
+    fn from_element(element: &Element) -> Option<Self> {
+        Some(Self {
+            name: element.string_attribute("name")?,
+            target_activity: element.string_attribute("targetActivity"),
+            exported: element.bool_attribute("exported"),
+            enabled: element.bool_attribute("enabled").unwrap_or(true),
+            has_intent_filter: false,
+        })
+    }
+
+    // without android:exported a component with intent filters is exported
+    fn is_exported(&self) -> bool {
+        self.exported.unwrap_or(self.has_intent_filter)
+    }
+}
+
+// why an intent filter accepting the URL does not receive the intent
+#[derive(Debug)]
+pub enum Exclusion {
+    Disabled,
+    NotExported,
+    // priority of the filter and the priority that wins
+    LowerPriority(i32, i32),
+}
+
+impl Exclusion {
+    pub fn to_string_human(&self) -> String {
+        match self {
+            Exclusion::Disabled => "the component is disabled".to_string(),
+            Exclusion::NotExported => "the component is not exported, other apps cannot start it".to_string(),
+            Exclusion::LowerPriority(priority, winner) => format!(
+                "its priority {} is lower than the priority {} of another filter",
+                priority, winner
+            ),
+        }
+    }
+}
+
+// the outcome of resolving a VIEW intent for the URL against the manifest
+#[derive(Debug)]
+pub struct Resolution {
+    pub receivers: Vec<UrlMatch>,
+    pub excluded: Vec<(UrlMatch, Exclusion)>,
+}
+
+impl Resolution {
+    // distinct components the user can choose from, more than one means a chooser is shown
+    pub fn components(&self) -> Vec<String> {
+        let mut res: Vec<String> = Vec::new();
+        for m in &self.receivers {
+            if !res.iter().any(|c| c == m.component_name()) {
+                res.push(m.component_name().to_string());
+            }
+        }
+        res
+    }
 }
 
 #[derive(Debug)]
 pub struct Manifest {
     app_id: Option<String>,
+    target_sdk_version: Option<i32>,
+    application_enabled: bool,
+    activities: Vec<Activity>,
     intent_filters: Vec<IntentFilter>,
+    // the <meta-data android:name="asset_statements"> of the application, usually a JSON string resource
+    asset_statements: Option<String>,
 }
 
 impl Manifest {
-    pub fn has_auto_verify(&self) -> bool {
-        self.intent_filters
+    // adds the intent filters of a split or feature module manifest
+    pub fn merge(&mut self, other: Manifest) {
+        if self.app_id.is_none() {
+            self.app_id = other.app_id;
+        }
+        if self.target_sdk_version.is_none() {
+            self.target_sdk_version = other.target_sdk_version;
+        }
+        if self.asset_statements.is_none() {
+            self.asset_statements = other.asset_statements;
+        }
+        self.activities.extend(other.activities);
+        self.intent_filters.extend(other.intent_filters);
+    }
+
+    // the package of <manifest>, after the build this is the application id
+    pub fn app_id(&self) -> Option<&str> {
+        self.app_id.as_ref().map(|p| &p[..])
+    }
+
+    pub fn asset_statements(&self) -> Option<&str> {
+        self.asset_statements.as_ref().map(|s| &s[..])
+    }
+
+    fn activity(&self, name: &str) -> Option<&Activity> {
+        let package = self.app_id.as_ref().map(|p| &p[..]);
+        let name = qualified_class_name(package, name);
+        self.activities
             .iter()
+            .find(|a| qualified_class_name(package, &a.name) == name)
+    }
+
+    // activities with intent filters must declare android:exported when targeting android 12
+    fn activities_missing_exported(&self) -> Vec<&Activity> {
+        match self.target_sdk_version {
+            Some(version) if version >= 31 => self.activities
+                .iter()
+                .filter(|a| a.has_intent_filter && a.exported.is_none())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // aliases whose targetActivity is not declared (before them), the package manager rejects those
+    fn aliases_without_target(&self) -> Vec<&Activity> {
+        let package = self.app_id.as_ref().map(|p| &p[..]);
+        self.activities
+            .iter()
+            .enumerate()
+            .filter(|&(i, alias)| match alias.target_activity {
+                Some(ref target) => !self.activities[..i].iter().any(|a| {
+                    a.target_activity.is_none()
+                        && qualified_class_name(package, &a.name) == qualified_class_name(package, target)
+                }),
+                None => false,
+            })
+            .map(|(_, alias)| alias)
+            .collect()
+    }
+
+    // models which components receive a VIEW intent for the URL. components must be enabled and exported,
+    // and only the filters with the highest priority are considered. apps that are not part of the system
+    // image cannot raise the priority of their filters above 0.
+    pub fn resolve(&self, url: &Uri, fragment: Option<&str>) -> Resolution {
+        let mut candidates = Vec::new();
+        let mut excluded = Vec::new();
+        for m in self.match_url(url, fragment) {
+            let component = self.activity(m.component_name());
+            let enabled = self.application_enabled && component.map(|c| c.enabled).unwrap_or(true);
+            let exported = component.map(|c| c.is_exported()).unwrap_or(true);
+            if !enabled {
+                excluded.push((m, Exclusion::Disabled));
+            } else if !exported {
+                excluded.push((m, Exclusion::NotExported));
+            } else {
+                candidates.push(m);
+            }
+        }
+
+        let effective_priority = |m: &UrlMatch| m.priority.min(0);
+        let highest = candidates.iter().map(|m| effective_priority(m)).max();
+        let mut receivers = Vec::new();
+        for m in candidates {
+            let priority = effective_priority(&m);
+            match highest {
+                Some(highest) if priority < highest => excluded.push((m, Exclusion::LowerPriority(priority, highest))),
+                _ => receivers.push(m),
+            }
+        }
+
+        Resolution { receivers, excluded }
+    }
+
+    // the filters relevant for app links, see IntentFilter::is_relevant
+    fn app_link_filters(&self) -> impl Iterator<Item = &IntentFilter> {
+        self.intent_filters.iter().filter(|f| f.is_relevant())
+    }
+
+    // the fully qualified classes of the activities that handle app links, aliases resolved to their target
+    pub fn handler_class_names(&self) -> Vec<String> {
+        let package = self.app_id.as_ref().map(|p| &p[..]);
+        let mut res: Vec<String> = Vec::new();
+        for filter in self.app_link_filters() {
+            let name = qualified_class_name(package, &filter.activity_name);
+            if !res.contains(&name) {
+                res.push(name);
+            }
+        }
+        res
+    }
+
+    pub fn has_auto_verify(&self) -> bool {
+        self.app_link_filters()
             .filter(|&f| f.auto_verify)
             .next()
             .is_some()
     }
 
+    // the hosts of the autoVerify filters together with the host whose assetlinks.json is fetched for them
+    pub fn verification_plan(&self) -> Vec<(Authority, Option<String>)> {
+        let mut res: Vec<(Authority, Option<String>)> = Vec::new();
+        for filter in self.app_link_filters().filter(|f| f.auto_verify) {
+            for authority in &filter.authorities {
+                if !res.iter().any(|r| r.0 == *authority) {
+                    let verification_host = authority.verification_host().map(|h| h.to_string());
+                    res.push((authority.clone(), verification_host));
+                }
+            }
+        }
+        res
+    }
+
     pub fn unique_authorities(&self) -> Vec<Authority> {
         let mut res = Vec::new();
-        for filter in &self.intent_filters {
+        for filter in self.app_link_filters() {
             for auth in &filter.authorities {
                 if !res.contains(auth) {
                     res.push(auth.clone());
@@ -249,6 +1170,120 @@ impl Manifest {
 
         res
     }
+
+    // the effective URL combinations per activity
+    pub fn data_combinations(&self) -> Vec<(String, Vec<DataCombination>)> {
+        self.app_link_filters()
+            .map(|f| (f.activity_name.clone(), f.data_combinations()))
+            .collect()
+    }
+
+    pub fn match_url(&self, url: &Uri, fragment: Option<&str>) -> Vec<UrlMatch> {
+        self.app_link_filters()
+            .filter_map(|f| f.match_url(url, fragment))
+            .collect()
+    }
+
+    // every scheme, host and path rule of all VIEW intent filters, custom schemes included
+    pub fn deep_links(&self) -> Vec<DeepLink> {
+        let mut res = Vec::new();
+        for filter in self.intent_filters.iter().filter(|f| f.is_deep_link()) {
+            for combination in filter.data_combinations() {
+                res.push(DeepLink {
+                    activity: filter.activity_name.clone(),
+                    alias: filter.alias_name.clone(),
+                    scheme: combination.scheme,
+                    host: combination.authority.as_ref().map(|a| a.host.clone()),
+                    port: combination.authority.as_ref().and_then(|a| a.port),
+                    path_rule: combination.path_matcher.map(|m| m.to_string()),
+                    auto_verify: filter.auto_verify,
+                });
+            }
+        }
+        res
+    }
+}
+
+#[test]
+fn test_resolve() {
+    fn start(name: &str, attributes: &[(&str, &str)]) -> ManifestEvent {
+        ManifestEvent::ElementStart(Element {
+            name: name.to_string(),
+            attributes: attributes
+                .iter()
+                .map(|&(n, v)| (n.to_string(), AttributeValue::String(v.to_string())))
+                .collect(),
+        })
+    }
+    fn end(name: &str) -> ManifestEvent {
+        ManifestEvent::ElementEnd(name.to_string())
+    }
+    fn filter(priority: &str) -> Vec<ManifestEvent> {
+        vec![
+            start("intent-filter", &[("autoVerify", "true"), ("priority", priority)]),
+            start("action", &[("name", "android.intent.action.VIEW")]),
+            end("action"),
+            start("category", &[("name", "android.intent.category.BROWSABLE")]),
+            end("category"),
+            start("data", &[("scheme", "https"), ("host", "example.com")]),
+            end("data"),
+            end("intent-filter"),
+        ]
+    }
+
+    let mut events = vec![
+        start("manifest", &[("package", "com.example")]),
+        start("uses-sdk", &[("targetSdkVersion", "31")]),
+        end("uses-sdk"),
+        start("application", &[]),
+        start("activity", &[("name", ".Main"), ("exported", "true")]),
+    ];
+    events.extend(filter("0"));
+    events.push(end("activity"));
+    events.push(start("activity", &[("name", ".Hidden"), ("exported", "false")]));
+    events.extend(filter("0"));
+    events.push(end("activity"));
+    events.push(start("activity", &[("name", ".Implicit")]));
+    events.extend(filter("-1"));
+    events.push(end("activity"));
+    events.push(start("activity-alias", &[("name", ".Alias"), ("targetActivity", "com.example.Main"), ("enabled", "false")]));
+    events.extend(filter("0"));
+    events.push(end("activity-alias"));
+    // filters after the alias must not be attributed to it
+    events.push(start("service", &[("name", ".Service")]));
+    events.extend(filter("0"));
+    events.push(end("service"));
+    events.push(end("application"));
+    events.push(end("manifest"));
+
+    let manifest = manifest_from_events(events);
+    let resolution = manifest.resolve(&Uri::from_str("https://example.com/").unwrap(), None);
+    assert_eq!(vec![".Main".to_string()], resolution.components());
+    let excluded: Vec<(&str, &Exclusion)> = resolution.excluded
+        .iter()
+        .map(|(m, e)| (m.component_name(), e))
+        .collect();
+    assert_eq!(3, excluded.len());
+    match excluded[0] {
+        (".Hidden", Exclusion::NotExported) => {}
+        e => panic!("unexpected exclusion {:?}", e),
+    }
+    match excluded[1] {
+        (".Alias", Exclusion::Disabled) => assert_eq!("com.example.Main", resolution.excluded[1].0.activity_name),
+        e => panic!("unexpected exclusion {:?}", e),
+    }
+    match excluded[2] {
+        (".Implicit", Exclusion::LowerPriority(-1, 0)) => {}
+        e => panic!("unexpected exclusion {:?}", e),
+    }
+
+    let missing: Vec<&str> = manifest.activities_missing_exported().iter().map(|a| &a.name[..]).collect();
+    assert_eq!(vec![".Implicit", ".Alias"], missing);
+    assert!(manifest.aliases_without_target().is_empty());
+    assert_eq!(
+        vec!["com.example.Main", "com.example.Hidden", "com.example.Implicit"],
+        manifest.handler_class_names()
+    );
 }
 
 #[derive(Debug)]
@@ -258,7 +1293,98 @@ pub enum Problem {
     IntentFilterContainsHttpAndCustomScheme(IntentFilter),
     // if an intent-filter contains http and non http schemes, the hosts in that intent-filter will not be autoverified
     NoMatchingIntenFilter,
-    MultipleMatchingIntentFilters,
+    // activities that accept the URL, the user will be shown a chooser
+    MultipleMatchingIntentFilters(Vec<String>),
+    // pattern and the reason android rejects it
+    InvalidPathAdvancedPattern(String, String),
+    // activity and the URL combinations that only exist because android merges the <data> elements
+    UnintendedDataCombinations(String, Vec<DataCombination>),
+    // intent filters accept the URL but none of their components receives the intent
+    NoReceivingActivity,
+    // activity with intent filters but without android:exported, android 12 refuses to install the app
+    MissingExportedAttribute(String),
+    // alias and its targetActivity
+    AliasTargetNotFound(String, String),
+    // package in the manifest and the app id that was asked for
+    AppIdMismatch(String, String),
+    MissingAssetStatements,
+    InvalidAssetStatements(String),
+    AssetStatements(assetlinks::Problem),
+    // the qualified class name of a handler activity
+    HandlerClassNotFound(String),
+}
+
+impl Problem {
+    pub fn to_string_human(&self) -> String {
+        match self {
+            Problem::InvalidApk => "The APK could not be read.".to_string(),
+            Problem::MissingAutoVerifyInManifest => {
+                "No intent-filter in the manifest has android:autoVerify=\"true\".".to_string()
+            }
+            Problem::IntentFilterContainsHttpAndCustomScheme(filter) => {
+                let schemes: Vec<&str> = filter.schemes
+                    .iter()
+                    .filter(|s| *s != "http" && *s != "https")
+                    .map(|s| &s[..])
+                    .collect();
+                format!(
+                    "An intent-filter of activity '{}' contains http(s) and custom schemes ({}). Its hosts will not be verified and the custom schemes are combined with all hosts.",
+                    filter.activity_name,
+                    schemes.join(", ")
+                )
+            }
+            Problem::NoMatchingIntenFilter => "No intent-filter in the manifest accepts the URL.".to_string(),
+            Problem::MultipleMatchingIntentFilters(activities) => format!(
+                "The URL is accepted by intent-filters of multiple activities: {}",
+                activities.join(", ")
+            ),
+            Problem::InvalidPathAdvancedPattern(pattern, reason) => format!(
+                "android:pathAdvancedPattern '{}' is invalid: {}",
+                pattern, reason
+            ),
+            Problem::UnintendedDataCombinations(activity, combinations) => {
+                let combinations: Vec<String> = combinations.iter().map(|c| c.to_string()).collect();
+                format!(
+                    "The <data> elements of an intent-filter of activity '{}' are merged, it also accepts: {}",
+                    activity,
+                    combinations.join(", ")
+                )
+            }
+            Problem::NoReceivingActivity => {
+                "Intent-filters accept the URL, but all of their activities are disabled, not exported or outranked.".to_string()
+            }
+            Problem::MissingExportedAttribute(activity) => format!(
+                "Activity '{}' has intent-filters but no android:exported attribute. Installing the app fails on Android 12 and later (targetSdkVersion 31+).",
+                activity
+            ),
+            Problem::AliasTargetNotFound(alias, target) => format!(
+                "The targetActivity '{}' of activity-alias '{}' is not declared before the alias.",
+                target, alias
+            ),
+            Problem::AppIdMismatch(package, app_id) => {
+                let mut res = format!(
+                    "The package of the manifest '{}' differs from the app id '{}'.",
+                    package, app_id
+                );
+                if let Some(suffix) = assetlinks::application_id_suffix(package, app_id) {
+                    res.push_str(&format!(
+                        " The difference '{}' looks like the applicationIdSuffix of a build type or flavor, check that the right variant was built.",
+                        suffix
+                    ));
+                }
+                res
+            }
+            Problem::MissingAssetStatements => {
+                "The manifest has no <meta-data android:name=\"asset_statements\"> in <application>, credentials are not shared with any site.".to_string()
+            }
+            Problem::InvalidAssetStatements(e) => format!("The asset_statements of the app are not valid: {}", e),
+            Problem::AssetStatements(problem) => problem.to_string_human(),
+            Problem::HandlerClassNotFound(class) => format!(
+                "The activity '{}' handles app links but the class is not in the dex files of the APK, check the name in the manifest.",
+                class
+            ),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -266,166 +1392,569 @@ pub struct CheckResult {
     sha256_fingerprint: Option<Vec<u8>>,
     pub manifest: Option<Manifest>,
     assetlinks: HashMap<String, Vec<Assetlink>>,
+    // empty if there is no code to check against, e.g. for source manifests
+    classes: HashSet<String>,
+}
+
+impl CheckResult {
+    pub fn sha256_fingerprint_string(&self) -> Option<String> {
+        self.sha256_fingerprint
+            .as_ref()
+            .map(|fp| signing::fingerprint_to_string(fp))
+    }
+
+    pub fn resolve(&self, url: &Uri, fragment: Option<&str>) -> Option<Resolution> {
+        self.manifest.as_ref().map(|m| m.resolve(url, fragment))
+    }
+
+    // the host whose assetlinks.json decides about the URL, differs from the URL's host for wildcard filters
+    pub fn verification_host(&self, url: &Uri, fragment: Option<&str>) -> Option<String> {
+        let resolution = self.resolve(url, fragment)?;
+        resolution.receivers
+            .iter()
+            .filter_map(|m| m.authority.as_ref())
+            .find(|a| a.is_wildcard())
+            .and_then(|a| a.verification_host())
+            .map(|h| h.to_string())
+    }
+
+    // the statements of the asset_statements resource, with the included statement lists fetched
+    pub fn app_statements<F>(&self, fetch: &mut F) -> (Vec<Assetlink>, Vec<Problem>)
+    where
+        F: FnMut(&str) -> Result<Vec<assetlinks::StatementEntry>, assetlinks::Error>,
+    {
+        let manifest = match self.manifest {
+            Some(ref manifest) => manifest,
+            None => return (Vec::new(), Vec::new()),
+        };
+        let statements = match manifest.asset_statements() {
+            Some(statements) => statements,
+            None => return (Vec::new(), vec![Problem::MissingAssetStatements]),
+        };
+        match assetlinks::parse_statements(statements) {
+            Ok(entries) => {
+                let (statements, problems) = assetlinks::resolve_includes(entries, fetch);
+                (statements, problems.into_iter().map(Problem::AssetStatements).collect())
+            }
+            Err(e) => (Vec::new(), vec![Problem::InvalidAssetStatements(e.to_string())]),
+        }
+    }
+
+    pub fn check_app_id(&self, app_id: &str) -> Vec<Problem> {
+        match self.manifest.as_ref().and_then(|m| m.app_id()) {
+            Some(package) if package != app_id => vec![Problem::AppIdMismatch(package.to_string(), app_id.to_string())],
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn get_problems(&self, url: &Uri, fragment: Option<&str>) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        if let Some(ref manifest) = self.manifest {
+            if !manifest.has_auto_verify() {
+                problems.push(Problem::MissingAutoVerifyInManifest);
+            }
+
+            for filter in manifest.app_link_filters() {
+                let mixed_schemes = filter.contains_http_scheme() && filter.contains_non_http_scheme();
+                if mixed_schemes {
+                    problems.push(Problem::IntentFilterContainsHttpAndCustomScheme(filter.clone()));
+                }
+                // custom schemes of a mixed filter are already reported above
+                let unintended: Vec<DataCombination> = filter.data_combinations()
+                    .into_iter()
+                    .filter(|c| !c.intended && (!mixed_schemes || c.scheme == "http" || c.scheme == "https"))
+                    .collect();
+                if !unintended.is_empty() {
+                    problems.push(Problem::UnintendedDataCombinations(filter.activity_name.clone(), unintended));
+                }
+
+                let group_matchers = filter.uri_relative_filter_groups
+                    .iter()
+                    .flat_map(|g| g.filters.iter().map(|f| &f.matcher));
+                for matcher in filter.path_matchers.iter().chain(group_matchers) {
+                    if let PathMatcher::AdvancedPattern(pattern) = matcher {
+                        if let Err(reason) = parse_advanced_pattern(pattern) {
+                            problems.push(Problem::InvalidPathAdvancedPattern(pattern.clone(), reason));
+                        }
+                    }
+                }
+            }
+
+            for activity in manifest.activities_missing_exported() {
+                problems.push(Problem::MissingExportedAttribute(activity.name.clone()));
+            }
+            for alias in manifest.aliases_without_target() {
+                let target = alias.target_activity.clone().unwrap_or_default();
+                problems.push(Problem::AliasTargetNotFound(alias.name.clone(), target));
+            }
+
+            let resolution = manifest.resolve(url, fragment);
+            let components = resolution.components();
+            if resolution.receivers.is_empty() && resolution.excluded.is_empty() {
+                problems.push(Problem::NoMatchingIntenFilter);
+            } else if resolution.receivers.is_empty() {
+                problems.push(Problem::NoReceivingActivity);
+            } else if components.len() > 1 {
+                problems.push(Problem::MultipleMatchingIntentFilters(components));
+            }
+
+            if !self.classes.is_empty() {
+                for class in manifest.handler_class_names() {
+                    if !self.classes.contains(&class) {
+                        problems.push(Problem::HandlerClassNotFound(class));
+                    }
+                }
+            }
+        }
+
+        problems
+    }
 }
 
+// accepts plain APKs, App Bundles (.aab), APK sets built by bundletool (.apks) and directories of split APKs
 pub fn check_apk(file_name: &str) -> io::Result<CheckResult> {
-    let apk_file = Apk::open(file_name)?;
-    let manifest = parse_manifest(&apk_file)?;
+    let path = Path::new(file_name);
+    let contents = if path.is_dir() {
+        bundle::read_split_apks(path)?
+    } else {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("aab") => bundle::read_app_bundle(path)?,
+            Some("apks") => bundle::read_apk_set(path)?,
+            _ => read_apk(file_name)?,
+        }
+    };
+
     let check_result = CheckResult {
-        sha256_fingerprint: None,
-        manifest: Some(manifest),
+        sha256_fingerprint: contents.certificates.first().map(|c| signing::sha256_fingerprint(c)),
+        manifest: Some(contents.manifest),
         assetlinks: HashMap::new(),
+        classes: contents.classes.into_iter().collect(),
     };
     Ok(check_result)
 }
 
-pub fn parse_manifest(apk: &Apk) -> io::Result<Manifest> {
-    let f = apk.file_by_name("AndroidManifest.xml")?;
-    if f.is_none() {
-        return Err(io::Error::new(io::ErrorKind::NotFound, "AndroidManifest.xml not found"));
+// checks a plain text manifest from the source tree or the manifest merger, there is no signature to check
+pub fn check_source_manifest(
+    file_name: &str,
+    placeholders: &HashMap<String, String>,
+    res_dirs: &[PathBuf],
+) -> io::Result<CheckResult> {
+    let manifest = source::read_source_manifest(Path::new(file_name), placeholders, res_dirs)?;
+    Ok(CheckResult {
+        sha256_fingerprint: None,
+        manifest: Some(manifest),
+        assetlinks: HashMap::new(),
+        classes: HashSet::new(),
+    })
+}
+
+// what is read from an APK, bundle or APK set
+#[derive(Debug)]
+pub struct ApkContents {
+    pub manifest: Manifest,
+    pub certificates: Vec<Vec<u8>>,
+    // the classes defined in the dex files
+    pub classes: Vec<String>,
+    // the resources the manifest was resolved against, splits resolve theirs against these as well
+    pub resources: ResourceTable,
+}
+
+impl ApkContents {
+    // the certificates of the base APK are kept, splits are signed with the same key
+    pub fn merge_split(&mut self, split: ApkContents) {
+        self.manifest.merge(split.manifest);
+        self.classes.extend(split.classes);
     }
-    let f = f.unwrap();
-    let mut res: Vec<IntentFilter> = Vec::new();
+}
+
+// reads the manifest, the signing certificates and the classes of a single APK
+pub fn read_apk(file_name: &str) -> io::Result<ApkContents> {
+    read_split_apk(file_name, &ResourceTable::default())
+}
+
+// the manifest of a feature split references resources of the base APK, e.g. @string/title for dist:title.
+// base holds the resources of the base APK, the split's own resources are added after them.
+pub fn read_split_apk(file_name: &str, base: &ResourceTable) -> io::Result<ApkContents> {
+    let apk_file = Apk::open(file_name)?;
+    let mut split_resources = ResourceTable::default();
+    if let Some(arsc) = read_apk_entry(&apk_file, "resources.arsc")? {
+        split_resources.add_arsc(&arsc)?;
+    }
+    let resources = split_resources_with_base(base, &split_resources);
+    let manifest = parse_manifest(&apk_file, &resources)?;
+
+    let buf = fs::read(file_name)?;
+    let mut zip = zip::ZipArchive::new(Cursor::new(&buf[..]))?;
+    let mut certificates = signing::certificates_from_signing_block(&buf)?;
+    if certificates.is_empty() {
+        certificates = signing::certificates_from_jar_signature(&mut zip)?;
+    }
+    let classes = dex::class_names_from_zip(&mut zip, "")?;
+
+    Ok(ApkContents {
+        manifest,
+        certificates,
+        classes,
+        resources,
+    })
+}
+
+fn split_resources_with_base(base: &ResourceTable, split: &ResourceTable) -> ResourceTable {
+    let mut resources = base.clone();
+    resources.merge(split);
+    resources
+}
+
+#[test]
+fn test_split_resources_with_base() {
+    let mut base = ResourceTable::default();
+    base.insert(0x7f0b_0001, resources::DEFAULT_CONFIG.to_string(), resources::Value::String("Shop".to_string()));
+    // the feature split's dist:title points at a string of the base
+    let mut split = ResourceTable::default();
+    split.insert(0x7e02_0000, resources::DEFAULT_CONFIG.to_string(), resources::Value::Reference(0x7f0b_0001));
+
+    assert!(resolve_reference(0x7e02_0000, &split).is_err());
+    let resources = split_resources_with_base(&base, &split);
+    match resolve_reference(0x7e02_0000, &resources).unwrap() {
+        Some(AttributeValue::String(title)) => assert_eq!("Shop", title),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AttributeValue {
+    String(String),
+    Boolean(bool),
+    Int(i32),
+    // a resource reference with different values per configuration, as pairs of qualifiers and value
+    Variants(Vec<(String, String)>),
+}
+
+// the value of a referenced resource, None for framework resources and resources without a plain value
+// (styles, arrays). fails if the resource is missing from the app's resource table.
+pub fn resolve_reference(id: u32, resources: &ResourceTable) -> io::Result<Option<AttributeValue>> {
+    if !resources::is_app_resource(id) {
+        return Ok(None);
+    }
+    let mut variants = resources.resolve(id)?;
+    Ok(match variants.len() {
+        0 => None,
+        _ if variants.iter().all(|v| v.1 == variants[0].1) => Some(AttributeValue::String(variants.remove(0).1)),
+        _ => Some(AttributeValue::Variants(variants)),
+    })
+}
+
+// the attributes the link checks read. labels, icons, themes and library attributes don't matter for them.
+pub fn is_checked_attribute(name: &str) -> bool {
+    match name {
+        "scheme" | "host" | "port" | "name" | "exported" | "enabled" | "priority" | "autoVerify" | "resource"
+        | "targetActivity" => true,
+        _ => name.starts_with("path") || name.starts_with("query") || name.starts_with("fragment"),
+    }
+}
+
+// like resolve_reference, but a reference of an attribute that is not checked is kept unresolved (None)
+// instead of failing the whole manifest when it is missing from the resource table
+pub fn resolve_attribute_reference(
+    attribute: &str,
+    id: u32,
+    resources: &ResourceTable,
+) -> io::Result<Option<AttributeValue>> {
+    match resolve_reference(id, resources) {
+        Err(_) if !is_checked_attribute(attribute) => Ok(None),
+        res => res,
+    }
+}
+
+#[test]
+fn test_resolve_attribute_reference() {
+    let mut resources = ResourceTable::default();
+    for &(config, value) in &[("default", "example.com"), ("de", "example.de"), ("fr", "example.de")] {
+        resources.insert(0x7f0b_0000, config.to_string(), resources::Value::String(value.to_string()));
+    }
+    match resolve_attribute_reference("host", 0x7f0b_0000, &resources).unwrap() {
+        Some(AttributeValue::Variants(variants)) => assert_eq!(3, variants.len()),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(resolve_attribute_reference("label", 0x7f0b_0001, &resources).unwrap().is_none());
+    assert!(resolve_attribute_reference("icon", 0x7f0b_0001, &resources).unwrap().is_none());
+    assert!(resolve_attribute_reference("host", 0x7f0b_0001, &resources).is_err());
+    assert!(resolve_attribute_reference("pathPrefix", 0x7f0b_0001, &resources).is_err());
+}
+
+// a manifest element independent of the encoding (binary AXML or protobuf) it was read from
+#[derive(Debug, Clone)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, AttributeValue)>,
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&AttributeValue> {
+        self.attributes.iter().find(|a| a.0 == name).map(|a| &a.1)
+    }
+
+    // the value of the default configuration for attributes that vary
+    fn string_attribute(&self, name: &str) -> Option<String> {
+        match self.attribute(name)? {
+            AttributeValue::String(s) => Some(s.clone()),
+            AttributeValue::Boolean(b) => Some(b.to_string()),
+            AttributeValue::Int(i) => Some(i.to_string()),
+            AttributeValue::Variants(variants) => variants
+                .iter()
+                .find(|v| v.0 == resources::DEFAULT_CONFIG)
+                .or_else(|| variants.first())
+                .map(|v| v.1.clone()),
+        }
+    }
+
+    // every distinct value the attribute can take
+    fn string_attribute_variants(&self, name: &str) -> Vec<String> {
+        match self.attribute(name) {
+            Some(AttributeValue::Variants(variants)) => {
+                let mut res: Vec<String> = Vec::new();
+                for (_, value) in variants {
+                    if !res.contains(value) {
+                        res.push(value.clone());
+                    }
+                }
+                res
+            }
+            _ => self.string_attribute(name).into_iter().collect(),
+        }
+    }
+
+    fn bool_attribute(&self, name: &str) -> Option<bool> {
+        match self.attribute(name)? {
+            AttributeValue::Boolean(b) => Some(*b),
+            AttributeValue::Int(_) => None,
+            _ => self.string_attribute(name)?.parse().ok(),
+        }
+    }
+
+    fn int_attribute(&self, name: &str) -> Option<i32> {
+        match self.attribute(name)? {
+            AttributeValue::Int(i) => Some(*i),
+            AttributeValue::Boolean(_) => None,
+            _ => self.string_attribute(name)?.parse().ok(),
+        }
+    }
+}
+
+pub enum ManifestEvent {
+    ElementStart(Element),
+    ElementEnd(String),
+}
+
+fn read_apk_entry(apk: &Apk, name: &str) -> io::Result<Option<Vec<u8>>> {
+    let f = match apk.file_by_name(name)? {
+        Some(f) => f,
+        None => return Ok(None),
+    };
     let mut data = Vec::with_capacity(f.len());
     let mut rdr = f.content()?;
     rdr.read_to_end(&mut data)?;
-    let resources = apk.get_resources().unwrap();
-    let mut app_id: Option<String> = None;
+    Ok(Some(data))
+}
 
+pub fn parse_manifest(apk: &Apk, resources: &ResourceTable) -> io::Result<Manifest> {
+    let data = match read_apk_entry(apk, "AndroidManifest.xml")? {
+        Some(data) => data,
+        None => return Err(io::Error::new(io::ErrorKind::NotFound, "AndroidManifest.xml not found")),
+    };
 
+    let mut events = Vec::new();
     if let Ok(it) = XmlElementStream::new(&data) {
-        let mut activity: Option<ElementStart> = None;
-        let mut intent_filter: Option<IntentFilter> = None;
         for e in it {
             match e {
-                XmlEvent::ElementStart(e) => {
-                    match &e.name[..] {
-                        "manifest" => app_id = get_string_attribute(&e, "package", &resources),
-                        "activity" => activity = Some(e),
-                        "activity-alias" => activity = Some(e),
-                        "intent-filter" if activity.is_some() => { // in case of intent-filter in <service> or <receiver> activity will be None
-                            let activity_name = get_string_attribute(&activity.as_ref().unwrap(), "name", resources);
-                            if let Some(activity_name) = activity_name {
-                                intent_filter = Some(IntentFilter::new(activity_name, get_intent_filter_auto_verify(&e)));
+                XmlEvent::ElementStart(e) => events.push(ManifestEvent::ElementStart(element_from_axml(&e, resources)?)),
+                XmlEvent::ElementEnd(e) => events.push(ManifestEvent::ElementEnd(e.name)),
+                _ => {}
+            }
+        }
+    }
+    Ok(manifest_from_events(events))
+}
+
+pub fn manifest_from_events(events: Vec<ManifestEvent>) -> Manifest {
+    let mut res: Vec<IntentFilter> = Vec::new();
+    let mut app_id: Option<String> = None;
+    let mut target_sdk_version: Option<i32> = None;
+    let mut application_enabled = true;
+    let mut activities: Vec<Activity> = Vec::new();
+    let mut activity: Option<Activity> = None;
+    let mut asset_statements: Option<String> = None;
+    // meta-data of services, receivers and providers is not the application's
+    let mut in_component = false;
+    let mut intent_filter: Option<IntentFilter> = None;
+    let mut filter_group: Option<UriRelativeFilterGroup> = None;
+    for e in events {
+        match e {
+            ManifestEvent::ElementStart(e) => {
+                match &e.name[..] {
+                    "manifest" => app_id = e.string_attribute("package"),
+                    "uses-sdk" => target_sdk_version = e.int_attribute("targetSdkVersion"),
+                    "application" => application_enabled = e.bool_attribute("enabled").unwrap_or(true),
+                    "activity" | "activity-alias" => {
+                        in_component = true;
+                        activity = Activity::from_element(&e);
+                    }
+                    "service" | "receiver" | "provider" => in_component = true,
+                    "meta-data" if !in_component => {
+                        if e.string_attribute("name").map(|n| n == "asset_statements").unwrap_or(false) {
+                            asset_statements = e.string_attribute("resource").or_else(|| e.string_attribute("value"));
+                        }
+                    }
+                    "intent-filter" if activity.is_some() => { // in case of intent-filter in <service> or <receiver> activity will be None
+                        let activity = activity.as_mut().unwrap();
+                        activity.has_intent_filter = true;
+                        let auto_verify = e.bool_attribute("autoVerify").unwrap_or(false);
+                        let mut filter = match activity.target_activity {
+                            Some(ref target) => {
+                                let mut filter = IntentFilter::new(target.clone(), auto_verify);
+                                filter.alias_name = Some(activity.name.clone());
+                                filter
                             }
+                            None => IntentFilter::new(activity.name.clone(), auto_verify),
+                        };
+                        filter.priority = e.int_attribute("priority").unwrap_or(0);
+                        intent_filter = Some(filter);
+                    }
+                    "action" if intent_filter.is_some() => {
+                        if let Some(action) = e.string_attribute("name") {
+                            let intent_filter = intent_filter.as_mut().unwrap();
+                            intent_filter.action.push(action);
                         }
-                        "action" if intent_filter.is_some() => {
-                            if let Some(action) = get_string_attribute(&e, "name", resources) {
-                                let intent_filter = intent_filter.as_mut().unwrap();
-                                intent_filter.action.push(action);
+                    }
+                    "uri-relative-filter-group" if intent_filter.is_some() => {
+                        filter_group = Some(UriRelativeFilterGroup {
+                            allow: e.bool_attribute("allow").unwrap_or(true),
+                            filters: Vec::new(),
+                        });
+                    }
+                    "data" if filter_group.is_some() => {
+                        let filter_group = filter_group.as_mut().unwrap();
+                        let parts = [("path", UriPart::Path), ("query", UriPart::Query), ("fragment", UriPart::Fragment)];
+                        for &(attribute, part) in parts.iter() {
+                            for matcher in PathMatcher::from_data_element(&e, attribute) {
+                                filter_group.filters.push(UriRelativeFilter { part, matcher });
                             }
                         }
-                        "data" if intent_filter.is_some() => {
-                            if let Some(mut intent_filter) = intent_filter.as_mut() {
-                                if let Some(scheme) = get_string_attribute(&e, "scheme", &resources) {
-                                    if !intent_filter.schemes.contains(&scheme) {
-                                        intent_filter.schemes.push(scheme);
-                                    }
-                                }
+                    }
+                    "data" if intent_filter.is_some() => {
+                        if let Some(intent_filter) = intent_filter.as_mut() {
+                            // schemes and hosts from resources can differ per configuration,
+                            // every variant is taken into account
+                            let schemes = e.string_attribute_variants("scheme");
+                            let port = e.int_attribute("port").map(|p| p as u16);
+                            let authorities: Vec<Authority> = e.string_attribute_variants("host")
+                                .into_iter()
+                                .map(|host| Authority { host, port })
+                                .collect();
+                            let path_matchers = PathMatcher::from_data_element(&e, "path");
 
-                                if let Some(host) = get_string_attribute(&e, "host", &resources) {
-                                    let p = get_int_attribute(&e, "port", &resources);
-                                    let port = if let Some(p) = get_int_attribute(&e, "port", &resources) {
-                                        Some(p as u16)
-                                    } else {
-                                        None
-                                    };
-
-                                    let authority = Authority {
-                                        host,
-                                        port,
-                                    };
-                                    if !intent_filter.authorities.contains(&authority) {
-                                        intent_filter.authorities.push(authority);
-                                    }
+                            let element_schemes: Vec<Option<&String>> = if schemes.is_empty() {
+                                vec![None]
+                            } else {
+                                schemes.iter().map(Some).collect()
+                            };
+                            let element_authorities: Vec<Option<&Authority>> = if authorities.is_empty() {
+                                vec![None]
+                            } else {
+                                authorities.iter().map(Some).collect()
+                            };
+                            for scheme in &element_schemes {
+                                for authority in &element_authorities {
+                                    intent_filter.data_elements.push(DataElement {
+                                        scheme: scheme.cloned(),
+                                        authority: authority.cloned(),
+                                        path_matchers: path_matchers.clone(),
+                                    });
                                 }
+                            }
 
-                                if let Some(p) = get_string_attribute(&e, "path", &resources) {
-                                    intent_filter.path_matchers.push(PathMatcher::Literal(p));
-                                }
-                                if let Some(p) = get_string_attribute(&e, "pathPrefix", &resources) {
-                                    intent_filter.path_matchers.push(PathMatcher::Prefix(p));
+                            for scheme in schemes {
+                                if !intent_filter.schemes.contains(&scheme) {
+                                    intent_filter.schemes.push(scheme);
                                 }
-                                if let Some(p) = get_string_attribute(&e, "pathPattern", &resources) {
-                                    intent_filter.path_matchers.push(PathMatcher::Literal(p));
+                            }
+
+                            for authority in authorities {
+                                if !intent_filter.authorities.contains(&authority) {
+                                    intent_filter.authorities.push(authority);
                                 }
                             }
+
+                            intent_filter.path_matchers.extend(path_matchers);
                         }
-                        "category" if intent_filter.is_some() => {
-                            if let Some(action) = get_string_attribute(&e, "name", resources) {
-                                let intent_filter = intent_filter.as_mut().unwrap();
-                                intent_filter.category.push(action);
-                            }
+                    }
+                    "category" if intent_filter.is_some() => {
+                        if let Some(action) = e.string_attribute("name") {
+                            let intent_filter = intent_filter.as_mut().unwrap();
+                            intent_filter.category.push(action);
                         }
-                        _ => {}
                     }
+                    _ => {}
                 }
-                XmlEvent::ElementEnd(e) => {
-                    match &e.name[..] {
-                        "activity" => activity = None,
-                        "intent-filter" => {
-                            if let Some(ref intent_filter) = intent_filter {
-                                if intent_filter.is_relevant() {
-                                    res.push(intent_filter.clone());
-                                }
+            }
+            ManifestEvent::ElementEnd(name) => {
+                match &name[..] {
+                    "activity" | "activity-alias" => {
+                        in_component = false;
+                        if let Some(activity) = activity.take() {
+                            activities.push(activity);
+                        }
+                    }
+                    "service" | "receiver" | "provider" => in_component = false,
+                    "uri-relative-filter-group" => {
+                        if let (Some(intent_filter), Some(filter_group)) = (intent_filter.as_mut(), filter_group.take()) {
+                            intent_filter.uri_relative_filter_groups.push(filter_group);
+                        }
+                    }
+                    "intent-filter" => {
+                        if let Some(intent_filter) = intent_filter.take() {
+                            if intent_filter.is_deep_link() {
+                                res.push(intent_filter);
                             }
                         }
-                        _ => {}
                     }
+                    _ => {}
                 }
-                _ => {}
             }
         }
     }
-    Ok(Manifest { app_id, intent_filters: res })
-}
-
-fn get_string_attribute(element: &ElementStart, field_name: &str, resources: &Resources) -> Option<String> {
-    if element.attribute_len() > 0 {
-        for a in element.attributes.as_ref().unwrap() {
-            if a.name == field_name {
-                let s = if a.value.is_reference_type() {
-                    match a.value {
-                        TypedValue::Reference(r) if is_package_reference(r) => resources.get_human_reference(r).unwrap(),
-                        TypedValue::Reference(_) => a.value.to_string(),
-                        _ => "".to_string(),
-                    }
-                } else {
-                    a.value.to_string()
-                };
-                return Some(s);
-            }
-        }
-    }
-
-    None
-}
-
-fn get_intent_filter_auto_verify(intent_filter: &ElementStart) -> bool {
-    if intent_filter.attribute_len() > 0 {
-        for a in intent_filter.attributes.as_ref().unwrap() {
-            if a.name == "autoVerify" {
-                if let TypedValue::Boolean(b) = a.value {
-                    return b;
-                }
-            }
-        }
+    Manifest {
+        app_id,
+        target_sdk_version,
+        application_enabled,
+        activities,
+        intent_filters: res,
+        asset_statements,
     }
-
-    false
 }
 
-
-fn get_int_attribute(element: &ElementStart, field_name: &str, resources: &Resources) -> Option<i32> {
+fn element_from_axml(element: &ElementStart, resources: &ResourceTable) -> io::Result<Element> {
+    let mut attributes = Vec::new();
     if element.attribute_len() > 0 {
         for a in element.attributes.as_ref().unwrap() {
-            if a.name == field_name {
-                if let TypedValue::IntDecimal(d) = a.value {
-                    return Some(d);
+            let value = match a.value {
+                TypedValue::Boolean(b) => AttributeValue::Boolean(b),
+                TypedValue::IntDecimal(d) => AttributeValue::Int(d),
+                TypedValue::Reference(r) => {
+                    let resolved = resolve_attribute_reference(&a.name, r, resources).map_err(|e| {
+                        io::Error::new(e.kind(), format!("{} (android:{} of <{}>)", e, a.name, element.name))
+                    })?;
+                    resolved.unwrap_or_else(|| AttributeValue::String(a.value.to_string()))
                 }
-            }
+                _ if a.value.is_reference_type() => AttributeValue::String("".to_string()),
+                _ => AttributeValue::String(a.value.to_string()),
+            };
+            attributes.push((a.name.clone(), value));
         }
     }
 
-    None
+    Ok(Element {
+        name: element.name.clone(),
+        attributes,
+    })
 }
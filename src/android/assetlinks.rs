@@ -4,17 +4,232 @@ use http::uri::Parts;
 
 //TODO: check robots.txt
 
+const HANDLE_ALL_URLS: &str = "delegate_permission/common.handle_all_urls";
+const GET_LOGIN_CREDS: &str = "delegate_permission/common.get_login_creds";
+
+// an android_app target has a package name and fingerprints, a web target a site
 #[derive(Debug, Deserialize)]
-pub struct AppTarget {
+pub struct Target {
     namespace: String,
-    package_name: String,
+    package_name: Option<String>,
+    #[serde(default)]
     sha256_cert_fingerprints: Vec<String>,
+    site: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Assetlink {
     relation: Vec<String>,
-    target: AppTarget,
+    target: Target,
+}
+
+impl Assetlink {
+    fn is_for_package(&self, package: &str) -> bool {
+        self.target.namespace == "android_app" && self.target.package_name.as_ref().map(|p| p == package).unwrap_or(false)
+    }
+
+    fn is_for_site(&self, site: &str) -> bool {
+        self.target.namespace == "web" && self.target.site.as_ref().map(|s| s == site).unwrap_or(false)
+    }
+
+    fn has_relation(&self, relation: &str) -> bool {
+        self.relation.iter().any(|r| r == relation)
+    }
+
+    pub fn to_string_human(&self) -> String {
+        match self.target.site {
+            Some(ref site) => format!("site '{}', relations: {}", site, self.relation.join(", ")),
+            None => format!(
+                "package '{}', relations: {}, fingerprints: {}",
+                self.target.package_name.as_ref().map(|p| &p[..]).unwrap_or(""),
+                self.relation.join(", "),
+                self.target.sha256_cert_fingerprints.join(", ")
+            ),
+        }
+    }
+}
+
+// an entry of a statement list: a statement, or the URL of another statement list
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum StatementEntry {
+    Include { include: String },
+    Statement(Assetlink),
+}
+
+// statement lists as found in the asset_statements resource of an app
+pub fn parse_statements(json: &str) -> Result<Vec<StatementEntry>, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+pub fn fetch_statements(url: &str) -> Result<Vec<StatementEntry>, Error> {
+    let mut res = reqwest::get(url)?;
+    if !res.status().is_success() {
+        return Err(Error::FetchFailed);
+    }
+    let mut buf = Vec::new();
+    res.read_to_end(&mut buf)?;
+    Ok(serde_json::from_slice(&buf)?)
+}
+
+// includes can point to lists with further includes, the depth is limited to protect against loops
+const MAX_INCLUDE_DEPTH: usize = 3;
+
+// replaces the include entries by the statements of the referenced lists
+pub fn resolve_includes<F>(entries: Vec<StatementEntry>, fetch: &mut F) -> (Vec<Assetlink>, Vec<Problem>)
+where
+    F: FnMut(&str) -> Result<Vec<StatementEntry>, Error>,
+{
+    let mut statements = Vec::new();
+    let mut problems = Vec::new();
+    let mut visited = Vec::new();
+    resolve_includes_into(entries, fetch, 0, &mut visited, &mut statements, &mut problems);
+    (statements, problems)
+}
+
+fn resolve_includes_into<F>(
+    entries: Vec<StatementEntry>,
+    fetch: &mut F,
+    depth: usize,
+    visited: &mut Vec<String>,
+    statements: &mut Vec<Assetlink>,
+    problems: &mut Vec<Problem>,
+) where
+    F: FnMut(&str) -> Result<Vec<StatementEntry>, Error>,
+{
+    for entry in entries {
+        match entry {
+            StatementEntry::Statement(statement) => statements.push(statement),
+            StatementEntry::Include { include } => {
+                if visited.contains(&include) {
+                    continue;
+                }
+                visited.push(include.clone());
+                if depth >= MAX_INCLUDE_DEPTH {
+                    problems.push(Problem::IncludeTooDeep(include));
+                    continue;
+                }
+                match fetch(&include) {
+                    Ok(included) => resolve_includes_into(included, fetch, depth + 1, visited, statements, problems),
+                    Err(_) => problems.push(Problem::IncludeFailed(include)),
+                }
+            }
+        }
+    }
+}
+
+// the website side of the statements an app declares: every site has to list the app with the same relations
+pub fn check_reciprocal<F>(statements: &[Assetlink], package: &str, fingerprint: Option<&str>, fetch: &mut F) -> Vec<Problem>
+where
+    F: FnMut(&str) -> Result<Vec<StatementEntry>, Error>,
+{
+    let mut problems = Vec::new();
+    let mut checked: Vec<&str> = Vec::new();
+    for site in statements.iter().filter_map(|s| s.target.site.as_ref()) {
+        if checked.contains(&&site[..]) {
+            continue;
+        }
+        checked.push(site);
+
+        let relations: Vec<&String> = statements
+            .iter()
+            .filter(|s| s.is_for_site(site))
+            .flat_map(|s| s.relation.iter())
+            .collect();
+        let url = format!("{}/.well-known/assetlinks.json", site.trim_end_matches('/'));
+        let site_statements = match fetch(&url) {
+            Ok(entries) => {
+                let (site_statements, include_problems) = resolve_includes(entries, fetch);
+                problems.extend(include_problems);
+                site_statements
+            }
+            Err(_) => {
+                problems.push(Problem::SiteUnreachable(site.clone()));
+                continue;
+            }
+        };
+
+        let for_package: Vec<&Assetlink> = site_statements.iter().filter(|s| s.is_for_package(package)).collect();
+        for relation in relations {
+            if !for_package.iter().any(|s| s.has_relation(relation)) {
+                problems.push(Problem::SiteDoesNotConfirm(site.clone(), relation.clone(), package.to_string()));
+            }
+        }
+        if let Some(fingerprint) = fingerprint {
+            let listed = for_package.iter().any(|s| {
+                s.target.sha256_cert_fingerprints
+                    .iter()
+                    .any(|fp| normalize_fingerprint(fp).map(|fp| fp == fingerprint).unwrap_or(false))
+            });
+            if !for_package.is_empty() && !listed {
+                problems.push(Problem::SiteFingerprintNotListed(site.clone(), fingerprint.to_string()));
+            }
+        }
+    }
+    problems
+}
+
+// the site of a web target for the URL: scheme, host and port without a path
+pub fn site_from_url(uri: &Uri) -> String {
+    let scheme = uri.scheme_part().map(|s| s.as_str()).unwrap_or("https");
+    match uri.port_part() {
+        Some(port) => format!("{}://{}:{}", scheme, uri.host().unwrap_or(""), port.as_u16()),
+        None => format!("{}://{}", scheme, uri.host().unwrap_or("")),
+    }
+}
+
+#[test]
+fn test_site_from_url() {
+    assert_eq!("https://example.com", site_from_url(&"https://example.com/login?a=b".parse().unwrap()));
+    assert_eq!("http://example.com:8080", site_from_url(&"http://example.com:8080/".parse().unwrap()));
+}
+
+// the site of a web target must be scheme and host only, anything else never matches
+fn check_site(site: &str) -> Option<Problem> {
+    let valid = match site.parse::<Uri>() {
+        Ok(uri) => uri.host().is_some() && !site.ends_with('/') && uri.path_and_query().map(|p| p.as_str().is_empty() || p.as_str() == "/").unwrap_or(true),
+        Err(_) => false,
+    };
+    if valid {
+        None
+    } else {
+        Some(Problem::InvalidSite(site.to_string()))
+    }
+}
+
+// checks the asset statements an app declares in its manifest for credential sharing with the site
+pub fn check_app_statements(statements: &[Assetlink], site: &str) -> Vec<Problem> {
+    let mut problems: Vec<Problem> = statements
+        .iter()
+        .filter_map(|s| s.target.site.as_ref())
+        .filter_map(|site| check_site(site))
+        .collect();
+    let shares = statements
+        .iter()
+        .any(|s| s.is_for_site(site) && s.has_relation(GET_LOGIN_CREDS));
+    if !shares {
+        problems.push(Problem::SiteNotInAppStatements(site.to_string()));
+    }
+    problems
+}
+
+// the applicationIdSuffix that turns one package name into the other, e.g. ".debug" for com.example and com.example.debug
+pub fn application_id_suffix<'a>(a: &'a str, b: &'a str) -> Option<&'a str> {
+    let (short, long) = if a.len() < b.len() { (a, b) } else { (b, a) };
+    if long.starts_with(short) && long[short.len()..].starts_with('.') {
+        Some(&long[short.len()..])
+    } else {
+        None
+    }
+}
+
+#[test]
+fn test_application_id_suffix() {
+    assert_eq!(Some(".debug"), application_id_suffix("com.example", "com.example.debug"));
+    assert_eq!(Some(".debug"), application_id_suffix("com.example.debug", "com.example"));
+    assert_eq!(None, application_id_suffix("com.example", "com.examples"));
+    assert_eq!(None, application_id_suffix("com.example", "com.example"));
+    assert_eq!(None, application_id_suffix("com.example", "org.example"));
 }
 
 #[derive(Debug)]
@@ -26,6 +241,25 @@ pub enum Problem {
     InvalidFileFormat,
     AppIdNotInAssetlinks,
     MissingHandleAllUrlsRelation,
+    MalformedFingerprint(String, Option<String>),
+    // the fingerprint, its number of bytes and the normalized form if it is also malformed
+    FingerprintWrongLength(String, usize, Option<String>),
+    DuplicateFingerprint(String),
+    // package of the app and a listed package that only differs by an applicationIdSuffix
+    PackageNotInAssetlinks(String, Option<String>),
+    MissingGetLoginCredsRelation(String),
+    // package and the fingerprint of the APK
+    GetLoginCredsFingerprintNotListed(String, String),
+    InvalidSite(String),
+    // the app does not declare get_login_creds for the site
+    SiteNotInAppStatements(String),
+    IncludeFailed(String),
+    IncludeTooDeep(String),
+    SiteUnreachable(String),
+    // site, relation and package
+    SiteDoesNotConfirm(String, String, String),
+    // site and the fingerprint of the APK
+    SiteFingerprintNotListed(String, String),
 }
 
 impl Problem {
@@ -46,13 +280,171 @@ impl Problem {
             Problem::InvalidFileFormat => "Failed to parse file.".to_string(),
             Problem::AppIdNotInAssetlinks => "The app id you specified was not found in assetlinks.json.".to_string(),
             Problem::MissingHandleAllUrlsRelation => "The entry for the specified app id is missing the relation 'delegate_permission/common.handle_all_urls'.".to_string(),
+            Problem::MalformedFingerprint(fp, Some(suggestion)) => format!(
+                "Fingerprint '{}' is malformed. Must be uppercase hex bytes separated by colons, e.g. '{}'.",
+                fp, suggestion
+            ),
+            Problem::MalformedFingerprint(fp, None) => format!(
+                "Fingerprint '{}' is malformed. Must be uppercase hex bytes separated by colons.",
+                fp
+            ),
+            Problem::FingerprintWrongLength(fp, len, suggestion) => {
+                let length = if *len == 20 {
+                    "has 20 bytes and looks like a SHA-1 fingerprint. A SHA-256 fingerprint (32 bytes) is required".to_string()
+                } else {
+                    format!("has {} bytes. A SHA-256 fingerprint must have 32 bytes", len)
+                };
+                match suggestion {
+                    Some(suggestion) => format!(
+                        "Fingerprint '{}' {}. It is also malformed, must be uppercase hex bytes separated by colons: '{}'.",
+                        fp, length, suggestion
+                    ),
+                    None => format!("Fingerprint '{}' {}.", fp, length),
+                }
+            }
+            Problem::DuplicateFingerprint(fp) => format!("Fingerprint '{}' is listed more than once.", fp),
+            Problem::PackageNotInAssetlinks(package, Some(similar)) => format!(
+                "The package '{}' of the app is not listed in assetlinks.json, but '{}' is. The difference '{}' looks like an applicationIdSuffix of a build type or flavor.",
+                package,
+                similar,
+                application_id_suffix(package, similar).unwrap_or_default()
+            ),
+            Problem::PackageNotInAssetlinks(package, None) => {
+                format!("The package '{}' of the app is not listed in assetlinks.json.", package)
+            }
+            Problem::MissingGetLoginCredsRelation(package) => format!(
+                "No statement for package '{}' has the relation '{}', credentials are not shared with the app.",
+                package, GET_LOGIN_CREDS
+            ),
+            Problem::GetLoginCredsFingerprintNotListed(package, fingerprint) => format!(
+                "The '{}' statements for package '{}' do not list the signing certificate fingerprint {} of the app.",
+                GET_LOGIN_CREDS, package, fingerprint
+            ),
+            Problem::InvalidSite(site) => format!(
+                "The site '{}' of a web target is invalid. It must consist of scheme and host only, e.g. 'https://example.com'.",
+                site
+            ),
+            Problem::SiteNotInAppStatements(site) => format!(
+                "The asset_statements of the app have no '{}' statement for the web target '{}'.",
+                GET_LOGIN_CREDS, site
+            ),
+            Problem::IncludeFailed(url) => format!("The included statement list '{}' could not be fetched or parsed.", url),
+            Problem::IncludeTooDeep(url) => format!(
+                "The statement list '{}' is nested more than {} includes deep and was not followed.",
+                url, MAX_INCLUDE_DEPTH
+            ),
+            Problem::SiteUnreachable(site) => format!("The assetlinks.json of site '{}' could not be fetched or parsed.", site),
+            Problem::SiteDoesNotConfirm(site, relation, package) => format!(
+                "The app declares the relation '{}' with site '{}', but the site has no statement with that relation for package '{}'.",
+                relation, site, package
+            ),
+            Problem::SiteFingerprintNotListed(site, fingerprint) => format!(
+                "The assetlinks.json of site '{}' does not list the signing certificate fingerprint {} of the app.",
+                site, fingerprint
+            ),
         }
     }
 }
 
+// normalises a fingerprint to the format expected by the android verifier:
+// uppercase hex bytes separated by colons. returns None if the value contains non hex characters.
+fn normalize_fingerprint(fingerprint: &str) -> Option<String> {
+    let hex: Vec<char> = fingerprint
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if hex.is_empty() || hex.len() % 2 != 0 || !hex.iter().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let bytes: Vec<String> = hex.chunks(2).map(|b| b.iter().collect()).collect();
+    Some(bytes.join(":"))
+}
+
+#[test]
+fn test_normalize_fingerprint() {
+    assert_eq!(Some("AB:CD:EF".to_string()), normalize_fingerprint("AB:CD:EF"));
+    assert_eq!(Some("AB:CD:EF".to_string()), normalize_fingerprint("ab:cd:ef"));
+    assert_eq!(Some("AB:CD:EF".to_string()), normalize_fingerprint("abcdef"));
+    assert_eq!(Some("AB:CD:EF".to_string()), normalize_fingerprint(" AB:CD:EF\n"));
+    assert_eq!(None, normalize_fingerprint("AB:CD:E"));
+    assert_eq!(None, normalize_fingerprint("AB:CD:XY"));
+    assert_eq!(None, normalize_fingerprint(""));
+}
+
+pub fn check_fingerprints(fingerprints: &[String]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut seen: Vec<String> = Vec::new();
+    for fp in fingerprints {
+        let normalized = match normalize_fingerprint(fp) {
+            Some(normalized) => normalized,
+            None => {
+                problems.push(Problem::MalformedFingerprint(fp.clone(), None));
+                continue;
+            }
+        };
+
+        let suggestion = if normalized != *fp { Some(normalized.clone()) } else { None };
+        // every byte takes two hex characters plus a colon, except for the last one
+        let len = (normalized.len() + 1) / 3;
+        if len != 32 {
+            problems.push(Problem::FingerprintWrongLength(fp.clone(), len, suggestion));
+        } else if suggestion.is_some() {
+            problems.push(Problem::MalformedFingerprint(fp.clone(), suggestion));
+        }
+
+        // different spellings of the same fingerprint are duplicates as well
+        if seen.contains(&normalized) {
+            problems.push(Problem::DuplicateFingerprint(fp.clone()));
+        } else {
+            seen.push(normalized);
+        }
+    }
+
+    problems
+}
+
+#[test]
+fn test_check_fingerprints() {
+    let valid = "14:6D:E9:83:C5:73:06:50:D8:EE:B9:95:2F:34:FC:64:16:A0:83:42:E6:1D:BE:A8:8A:04:96:B2:3F:CF:44:E5";
+    assert!(check_fingerprints(&[valid.to_string()]).is_empty());
+
+    let problems = check_fingerprints(&[valid.to_lowercase()]);
+    assert_eq!(1, problems.len());
+    match problems[0] {
+        Problem::MalformedFingerprint(_, Some(ref suggestion)) => assert_eq!(valid, suggestion),
+        _ => panic!("expected MalformedFingerprint"),
+    }
+
+    let problems = check_fingerprints(&[valid.to_string(), valid.replace(":", "")]);
+    assert_eq!(2, problems.len());
+    match (&problems[0], &problems[1]) {
+        (Problem::MalformedFingerprint(_, Some(_)), Problem::DuplicateFingerprint(_)) => {}
+        _ => panic!("expected MalformedFingerprint and DuplicateFingerprint"),
+    }
+
+    let sha1 = "DA:39:A3:EE:5E:6B:4B:0D:32:55:BF:EF:95:60:18:90:AF:D8:07:09";
+    let problems = check_fingerprints(&[sha1.to_string()]);
+    assert_eq!(1, problems.len());
+    match problems[0] {
+        Problem::FingerprintWrongLength(_, 20, None) => {}
+        _ => panic!("expected FingerprintWrongLength"),
+    }
+
+    let problems = check_fingerprints(&[sha1.to_lowercase()]);
+    assert_eq!(1, problems.len());
+    match problems[0] {
+        Problem::FingerprintWrongLength(_, 20, Some(ref suggestion)) => assert_eq!(sha1, suggestion),
+        _ => panic!("expected FingerprintWrongLength"),
+    }
+}
 
 pub fn assetlinks_json_from_url(uri: &Uri) -> Uri {
-    let host = uri.host().unwrap();
+    assetlinks_json_for_host(uri.host().unwrap())
+}
+
+pub fn assetlinks_json_for_host(host: &str) -> Uri {
     let mut parts = Parts::default();
     parts.authority = Some(host.parse().unwrap());
     parts.path_and_query = Some("/.well-known/assetlinks.json".parse().unwrap());
@@ -97,6 +489,65 @@ impl CheckResult {
         }
     }
 
+    // the statements that apply to an app with the given package
+    pub fn statements_for_package(&self, package: &str) -> Vec<&Assetlink> {
+        match self.content_parsed {
+            Some(ref assetlinks) => assetlinks
+                .iter()
+                .filter(|a| a.is_for_package(package))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // checks the package the app actually has, which can differ from the app id given on the command line
+    pub fn check_package(&self, package: &str) -> Vec<Problem> {
+        let assetlinks = match self.content_parsed {
+            Some(ref assetlinks) => assetlinks,
+            None => return Vec::new(),
+        };
+        if !self.statements_for_package(package).is_empty() {
+            return Vec::new();
+        }
+        let similar = assetlinks
+            .iter()
+            .filter_map(|a| a.target.package_name.as_ref())
+            .find(|p| application_id_suffix(package, p).is_some())
+            .cloned();
+        vec![Problem::PackageNotInAssetlinks(package.to_string(), similar)]
+    }
+
+    // the website side of credential sharing: a get_login_creds statement for the package listing the app's fingerprint
+    pub fn check_login_creds(&self, package: &str, fingerprint: Option<&str>) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        let statements: Vec<&Assetlink> = self.statements_for_package(package)
+            .into_iter()
+            .filter(|s| s.has_relation(GET_LOGIN_CREDS))
+            .collect();
+        if statements.is_empty() {
+            problems.push(Problem::MissingGetLoginCredsRelation(package.to_string()));
+        } else if let Some(fingerprint) = fingerprint {
+            let listed = statements.iter().any(|s| {
+                s.target.sha256_cert_fingerprints
+                    .iter()
+                    .any(|fp| normalize_fingerprint(fp).map(|fp| fp == fingerprint).unwrap_or(false))
+            });
+            if !listed {
+                problems.push(Problem::GetLoginCredsFingerprintNotListed(package.to_string(), fingerprint.to_string()));
+            }
+        }
+
+        if let Some(ref assetlinks) = self.content_parsed {
+            problems.extend(
+                assetlinks
+                    .iter()
+                    .filter_map(|s| s.target.site.as_ref())
+                    .filter_map(|site| check_site(site)),
+            );
+        }
+        problems
+    }
+
     pub fn get_problems(&self) -> Vec<Problem> {
         let mut problems = Vec::new();
         if let Some(sc) = self.status_code {
@@ -118,14 +569,18 @@ impl CheckResult {
         }
 
         if let Some(ref assetlinks) = self.content_parsed {
+            for assetlink in assetlinks {
+                problems.extend(check_fingerprints(&assetlink.target.sha256_cert_fingerprints));
+            }
+
             let matches: Vec<&Assetlink> = assetlinks.iter()
-                .filter(|&assetlink| assetlink.target.package_name == self.app_id)
+                .filter(|&assetlink| assetlink.is_for_package(&self.app_id))
                 .collect();
             if matches.is_empty() {
                 problems.push(Problem::AppIdNotInAssetlinks);
             } else {
                 let matches: Vec<&Assetlink> = matches.iter()
-                    .filter(|&&assetlink| assetlink.relation.contains(&"delegate_permission/common.handle_all_urls".into()))
+                    .filter(|&&assetlink| assetlink.has_relation(HANDLE_ALL_URLS))
                     .map(|&assetlink| assetlink)
                     .collect();
                 if matches.is_empty() {
@@ -138,6 +593,113 @@ impl CheckResult {
     }
 }
 
+#[test]
+fn test_check_package() {
+    let json = r#"[{
+        "relation": ["delegate_permission/common.handle_all_urls"],
+        "target": {"namespace": "android_app", "package_name": "com.example", "sha256_cert_fingerprints": []}
+    }]"#;
+    let mut check_res = CheckResult::new("https://example.com/.well-known/assetlinks.json".parse().unwrap(), "com.example".into());
+    check_res.content_parsed = Some(serde_json::from_str(json).unwrap());
+
+    assert_eq!(1, check_res.statements_for_package("com.example").len());
+    assert!(check_res.check_package("com.example").is_empty());
+    match check_res.check_package("com.example.debug")[..] {
+        [Problem::PackageNotInAssetlinks(_, Some(ref similar))] => assert_eq!("com.example", similar),
+        ref p => panic!("unexpected problems {:?}", p),
+    }
+    match check_res.check_package("org.example")[..] {
+        [Problem::PackageNotInAssetlinks(_, None)] => {}
+        ref p => panic!("unexpected problems {:?}", p),
+    }
+}
+
+#[test]
+fn test_check_login_creds() {
+    let fingerprint = "14:6D:E9:83:C5:73:06:50:D8:EE:B9:95:2F:34:FC:64:16:A0:83:42:E6:1D:BE:A8:8A:04:96:B2:3F:CF:44:E5";
+    let json = format!(r#"[{{
+        "relation": ["delegate_permission/common.handle_all_urls", "delegate_permission/common.get_login_creds"],
+        "target": {{"namespace": "android_app", "package_name": "com.example", "sha256_cert_fingerprints": ["{}"]}}
+    }}, {{
+        "relation": ["delegate_permission/common.get_login_creds"],
+        "target": {{"namespace": "web", "site": "https://example.com/"}}
+    }}]"#, fingerprint.to_lowercase());
+    let mut check_res = CheckResult::new("https://example.com/.well-known/assetlinks.json".parse().unwrap(), "com.example".into());
+    check_res.content_parsed = Some(serde_json::from_str(&json).unwrap());
+
+    match check_res.check_login_creds("com.example", Some(fingerprint))[..] {
+        [Problem::InvalidSite(ref site)] => assert_eq!("https://example.com/", site),
+        ref p => panic!("unexpected problems {:?}", p),
+    }
+    match check_res.check_login_creds("com.example", Some("AA:BB"))[..] {
+        [Problem::GetLoginCredsFingerprintNotListed(..), Problem::InvalidSite(_)] => {}
+        ref p => panic!("unexpected problems {:?}", p),
+    }
+    match check_res.check_login_creds("com.other", None)[..] {
+        [Problem::MissingGetLoginCredsRelation(_), Problem::InvalidSite(_)] => {}
+        ref p => panic!("unexpected problems {:?}", p),
+    }
+
+    let app_statements: Vec<Assetlink> = serde_json::from_str(r#"[{
+        "relation": ["delegate_permission/common.get_login_creds"],
+        "target": {"namespace": "web", "site": "https://example.com"}
+    }]"#).unwrap();
+    assert!(check_app_statements(&app_statements, "https://example.com").is_empty());
+    match check_app_statements(&app_statements, "https://example.org")[..] {
+        [Problem::SiteNotInAppStatements(_)] => {}
+        ref p => panic!("unexpected problems {:?}", p),
+    }
+}
+
+#[test]
+fn test_app_statements() {
+    let entries = parse_statements(r#"[
+        {"include": "https://example.com/.well-known/assetlinks.json"},
+        {"include": "https://example.com/missing.json"},
+        {"relation": ["delegate_permission/common.handle_all_urls"],
+         "target": {"namespace": "web", "site": "https://example.org"}}
+    ]"#).unwrap();
+    let site_json = r#"[{
+        "relation": ["delegate_permission/common.get_login_creds"],
+        "target": {"namespace": "android_app", "package_name": "com.example", "sha256_cert_fingerprints": ["AA:BB"]}
+    }, {
+        "relation": ["delegate_permission/common.get_login_creds"],
+        "target": {"namespace": "web", "site": "https://example.com"}
+    }]"#;
+    let mut fetched = Vec::new();
+    let mut fetch = |url: &str| {
+        fetched.push(url.to_string());
+        match url {
+            "https://example.com/.well-known/assetlinks.json" => Ok(parse_statements(site_json).unwrap()),
+            "https://example.org/.well-known/assetlinks.json" => Ok(Vec::new()),
+            _ => Err(Error::FetchFailed),
+        }
+    };
+
+    let (statements, problems) = resolve_includes(entries, &mut fetch);
+    assert_eq!(3, statements.len());
+    match problems[..] {
+        [Problem::IncludeFailed(ref url)] => assert_eq!("https://example.com/missing.json", url),
+        ref p => panic!("unexpected problems {:?}", p),
+    }
+
+    let problems = check_reciprocal(&statements, "com.example", Some("AA:BB"), &mut fetch);
+    match problems[..] {
+        [Problem::SiteDoesNotConfirm(ref site, ref relation, _)] => {
+            assert_eq!("https://example.org", site);
+            assert_eq!(HANDLE_ALL_URLS, relation);
+        }
+        ref p => panic!("unexpected problems {:?}", p),
+    }
+    let problems = check_reciprocal(&statements, "com.example", Some("CC:DD"), &mut fetch);
+    assert!(problems.iter().any(|p| match p {
+        Problem::SiteFingerprintNotListed(..) => true,
+        _ => false,
+    }));
+    drop(fetch);
+    assert!(fetched.contains(&"https://example.com/missing.json".to_string()));
+}
+
 #[derive(Debug)]
 pub enum Error {
     FetchFailed,
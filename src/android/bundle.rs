@@ -0,0 +1,407 @@
+use crate::android::apk::{self, ApkContents, AttributeValue, Element, Manifest, ManifestEvent};
+use crate::android::dex;
+use crate::android::protobuf::{self, Value};
+use crate::android::resources::{self, ResourceTable};
+use crate::android::signing;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek};
+use std::path::Path;
+use std::process;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+// App Bundles store the manifest and resources of every module as aapt2 protobuf messages.
+// see: https://android.googlesource.com/platform/frameworks/base/+/refs/heads/master/tools/aapt2/Resources.proto
+
+const MODULE_MANIFEST: &str = "/manifest/AndroidManifest.xml";
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn bytes(value: Value) -> io::Result<&[u8]> {
+    value
+        .as_bytes()
+        .ok_or_else(|| invalid("expected a length delimited field"))
+}
+
+// reads the nested {PackageId,TypeId,EntryId}.id message
+fn id_field(fields: &[(u32, Value)]) -> io::Result<u32> {
+    match protobuf::first(fields, 1) {
+        Some(id) => {
+            let id = protobuf::fields(bytes(id)?)?;
+            Ok(protobuf::first(&id, 1).and_then(|v| v.as_u64()).unwrap_or(0) as u32)
+        }
+        None => Ok(0),
+    }
+}
+
+// adds the entries of a resource table (resources.pb) in all configurations
+pub fn add_proto_table(resources: &mut ResourceTable, buf: &[u8]) -> io::Result<()> {
+    let table = protobuf::fields(buf)?;
+    for package in protobuf::all(&table, 2) {
+        let package = protobuf::fields(bytes(package)?)?;
+        let package_id = id_field(&package)?;
+        for resource_type in protobuf::all(&package, 3) {
+            let resource_type = protobuf::fields(bytes(resource_type)?)?;
+            let type_id = id_field(&resource_type)?;
+            for entry in protobuf::all(&resource_type, 3) {
+                let entry = protobuf::fields(bytes(entry)?)?;
+                let entry_id = id_field(&entry)?;
+                let id = package_id << 24 | type_id << 16 | entry_id;
+                for config_value in protobuf::all(&entry, 6) {
+                    let config_value = protobuf::fields(bytes(config_value)?)?;
+                    let config = match protobuf::first(&config_value, 1) {
+                        Some(config) => describe_config(bytes(config)?)?,
+                        None => resources::DEFAULT_CONFIG.to_string(),
+                    };
+                    resources.insert(id, config, config_value_value(&config_value)?);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// the mcc, mnc and locale qualifiers of a Configuration message
+fn describe_config(config: &[u8]) -> io::Result<String> {
+    let fields = protobuf::fields(config)?;
+    let mut qualifiers = Vec::new();
+    if let Some(mcc) = protobuf::first(&fields, 1).and_then(|v| v.as_u64()) {
+        qualifiers.push(format!("mcc{}", mcc));
+    }
+    if let Some(mnc) = protobuf::first(&fields, 2).and_then(|v| v.as_u64()) {
+        qualifiers.push(format!("mnc{}", mnc));
+    }
+    if let Some(locale) = protobuf::first(&fields, 3).and_then(|v| v.as_string()) {
+        qualifiers.push(locale);
+    }
+
+    Ok(if !qualifiers.is_empty() {
+        qualifiers.join("-")
+    } else if fields.is_empty() {
+        resources::DEFAULT_CONFIG.to_string()
+    } else {
+        "other".to_string()
+    })
+}
+
+// ConfigValue.value -> Value.item -> Item.ref, Item.str, Item.raw_str or Item.prim
+fn config_value_value(config_value: &[(u32, Value)]) -> io::Result<resources::Value> {
+    let value = match protobuf::first(config_value, 2) {
+        Some(value) => protobuf::fields(bytes(value)?)?,
+        None => return Ok(resources::Value::Complex),
+    };
+    let item = match protobuf::first(&value, 4) {
+        Some(item) => protobuf::fields(bytes(item)?)?,
+        None => return Ok(resources::Value::Complex),
+    };
+    item_value(&item)
+}
+
+fn item_value(item: &[(u32, Value)]) -> io::Result<resources::Value> {
+    if let Some(reference) = protobuf::first(item, 1) {
+        let reference = protobuf::fields(bytes(reference)?)?;
+        let id = protobuf::first(&reference, 2).and_then(|v| v.as_u64()).unwrap_or(0);
+        return Ok(resources::Value::Reference(id as u32));
+    }
+    if let Some(string) = protobuf::first(item, 2).or_else(|| protobuf::first(item, 3)) {
+        let string = protobuf::fields(bytes(string)?)?;
+        let string = protobuf::first(&string, 1).and_then(|v| v.as_string()).unwrap_or_default();
+        return Ok(resources::Value::String(string));
+    }
+    if let Some(primitive) = protobuf::first(item, 7) {
+        let primitive = protobuf::fields(bytes(primitive)?)?;
+        if let Some(b) = protobuf::first(&primitive, 8).and_then(|v| v.as_u64()) {
+            return Ok(resources::Value::Boolean(b != 0));
+        }
+        if let Some(i) = protobuf::first(&primitive, 6).and_then(|v| v.as_u64()) {
+            return Ok(resources::Value::Int(i as i32));
+        }
+    }
+    Ok(resources::Value::Complex)
+}
+
+fn attribute_value(name: &str, attribute: &[(u32, Value)], resources: &ResourceTable) -> io::Result<AttributeValue> {
+    let raw = protobuf::first(attribute, 3)
+        .and_then(|v| v.as_string())
+        .unwrap_or_default();
+
+    // XmlAttribute.compiled_item
+    if let Some(item) = protobuf::first(attribute, 6) {
+        let item = protobuf::fields(bytes(item)?)?;
+        match item_value(&item)? {
+            resources::Value::Reference(id) => {
+                if let Some(value) = apk::resolve_attribute_reference(name, id, resources)? {
+                    return Ok(value);
+                }
+            }
+            resources::Value::Boolean(b) => return Ok(AttributeValue::Boolean(b)),
+            resources::Value::Int(i) => return Ok(AttributeValue::Int(i)),
+            _ => {}
+        }
+    }
+
+    Ok(AttributeValue::String(raw))
+}
+
+// walks a XmlNode and its children depth first
+fn xml_events(node: &[u8], resources: &ResourceTable, events: &mut Vec<ManifestEvent>) -> io::Result<()> {
+    let node = protobuf::fields(node)?;
+    let element = match protobuf::first(&node, 1) {
+        Some(element) => protobuf::fields(bytes(element)?)?,
+        None => return Ok(()), // text node
+    };
+
+    let name = protobuf::first(&element, 3)
+        .and_then(|v| v.as_string())
+        .unwrap_or_default();
+    let mut attributes = Vec::new();
+    for attribute in protobuf::all(&element, 4) {
+        let attribute = protobuf::fields(bytes(attribute)?)?;
+        let attribute_name = protobuf::first(&attribute, 2)
+            .and_then(|v| v.as_string())
+            .unwrap_or_default();
+        let value = attribute_value(&attribute_name, &attribute, resources).map_err(|e| {
+            io::Error::new(e.kind(), format!("{} (android:{} of <{}>)", e, attribute_name, name))
+        })?;
+        attributes.push((attribute_name, value));
+    }
+
+    events.push(ManifestEvent::ElementStart(Element {
+        name: name.clone(),
+        attributes,
+    }));
+    for child in protobuf::all(&element, 5) {
+        xml_events(bytes(child)?, resources, events)?;
+    }
+    events.push(ManifestEvent::ElementEnd(name));
+
+    Ok(())
+}
+
+pub fn parse_proto_manifest(buf: &[u8], resources: &ResourceTable) -> io::Result<Manifest> {
+    let mut events = Vec::new();
+    xml_events(buf, resources, &mut events)?;
+    Ok(apk::manifest_from_events(events))
+}
+
+fn read_zip_entry<R: Read + Seek>(zip: &mut ZipArchive<R>, name: &str) -> io::Result<Option<Vec<u8>>> {
+    match zip.by_name(name) {
+        Ok(mut file) => {
+            let mut buf = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut buf)?;
+            Ok(Some(buf))
+        }
+        Err(ZipError::FileNotFound) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn zip_entry_names<R: Read + Seek>(zip: &mut ZipArchive<R>) -> io::Result<Vec<String>> {
+    let mut names = Vec::with_capacity(zip.len());
+    for i in 0..zip.len() {
+        names.push(zip.by_index(i)?.name().to_string());
+    }
+    Ok(names)
+}
+
+pub fn read_app_bundle(path: &Path) -> io::Result<ApkContents> {
+    let mut zip = ZipArchive::new(File::open(path)?)?;
+
+    let mut modules: Vec<String> = zip_entry_names(&mut zip)?
+        .iter()
+        .filter(|name| name.ends_with(MODULE_MANIFEST))
+        .map(|name| name[..name.len() - MODULE_MANIFEST.len()].to_string())
+        .collect();
+    if !modules.contains(&"base".to_string()) {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "base/manifest/AndroidManifest.xml not found"));
+    }
+    // the base module has to come first, it defines the package
+    modules.sort_by_key(|m| (m != "base", m.clone()));
+
+    // feature modules can reference resources of the base module, so all tables are combined
+    let mut resources = ResourceTable::default();
+    for module in &modules {
+        if let Some(buf) = read_zip_entry(&mut zip, &format!("{}/resources.pb", module))? {
+            add_proto_table(&mut resources, &buf)?;
+        }
+    }
+
+    let mut manifest: Option<Manifest> = None;
+    let mut classes = Vec::new();
+    for module in &modules {
+        classes.extend(dex::class_names_from_zip(&mut zip, &format!("{}/dex/", module))?);
+        let buf = read_zip_entry(&mut zip, &format!("{}{}", module, MODULE_MANIFEST))?.unwrap();
+        let module_manifest = parse_proto_manifest(&buf, &resources)?;
+        match manifest {
+            Some(ref mut manifest) => manifest.merge(module_manifest),
+            None => manifest = Some(module_manifest),
+        }
+    }
+
+    // bundles are signed with jarsigner, the APK signature schemes are only applied to the generated APKs
+    let certificates = signing::certificates_from_jar_signature(&mut zip)?;
+
+    Ok(ApkContents {
+        manifest: manifest.unwrap(),
+        certificates,
+        classes,
+        resources,
+    })
+}
+
+// apk_rs can only open APKs from the file system, so APKs inside an APK set are extracted to a temporary file
+fn read_embedded_apk<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    name: &str,
+    base: &ResourceTable,
+) -> io::Result<ApkContents> {
+    let buf = read_zip_entry(zip, name)?.unwrap();
+    let tmp = env::temp_dir().join(format!("ulverify-{}-{}", process::id(), name.replace('/', "_")));
+    fs::write(&tmp, &buf)?;
+    let res = apk::read_split_apk(&tmp.to_string_lossy(), base);
+    let _ = fs::remove_file(&tmp);
+    res
+}
+
+// APK sets (.apks) as built by bundletool contain split APKs in splits/ or standalone APKs in standalones/.
+// only the master splits contain components, configuration splits only add resources.
+pub fn read_apk_set(path: &Path) -> io::Result<ApkContents> {
+    let mut zip = ZipArchive::new(File::open(path)?)?;
+    let names = zip_entry_names(&mut zip)?;
+
+    let mut apks: Vec<&String> = names
+        .iter()
+        .filter(|name| name.starts_with("splits/") && name.ends_with("-master.apk"))
+        .collect();
+    apks.sort_by_key(|name| (*name != "splits/base-master.apk", name.to_string()));
+    if apks.is_empty() {
+        let standalone = names
+            .iter()
+            .find(|name| name.starts_with("standalones/") && name.ends_with(".apk"))
+            .or_else(|| names.iter().find(|name| *name == "universal.apk"));
+        if let Some(standalone) = standalone {
+            apks.push(standalone);
+        }
+    }
+    if apks.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no APKs found in APK set"));
+    }
+
+    // the base is read first, the manifests of feature splits reference its resources
+    let mut contents = read_embedded_apk(&mut zip, apks[0], &ResourceTable::default())?;
+    for name in &apks[1..] {
+        let split = read_embedded_apk(&mut zip, name, &contents.resources)?;
+        contents.merge_split(split);
+    }
+
+    Ok(contents)
+}
+
+// a directory of split APKs as pulled from a device: base.apk, split_<feature>.apk and split_config.*.apk
+pub fn read_split_apks(path: &Path) -> io::Result<ApkContents> {
+    let mut apks = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.ends_with(".apk") && !name.starts_with("split_config.") {
+            apks.push((name, entry.path()));
+        }
+    }
+    apks.sort_by_key(|apk| (apk.0 != "base.apk", apk.0.clone()));
+    if apks.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no APKs found in directory"));
+    }
+
+    let mut contents = apk::read_apk(&apks[0].1.to_string_lossy())?;
+    for apk in &apks[1..] {
+        let split = apk::read_split_apk(&apk.1.to_string_lossy(), &contents.resources)?;
+        contents.merge_split(split);
+    }
+
+    Ok(contents)
+}
+
+#[test]
+fn test_parse_proto_manifest() {
+    fn field(number: u32, content: &[u8]) -> Vec<u8> {
+        let mut res = vec![(number << 3 | 2) as u8];
+        let mut len = content.len();
+        while len >= 0x80 {
+            res.push((len & 0x7f) as u8 | 0x80);
+            len >>= 7;
+        }
+        res.push(len as u8);
+        res.extend_from_slice(content);
+        res
+    }
+    fn attribute(name: &str, value: &str) -> Vec<u8> {
+        let mut res = field(2, name.as_bytes());
+        res.extend(field(3, value.as_bytes()));
+        res
+    }
+    fn element(name: &str, attributes: &[Vec<u8>], children: &[Vec<u8>]) -> Vec<u8> {
+        let mut res = field(3, name.as_bytes());
+        for a in attributes {
+            res.extend(field(4, a));
+        }
+        for c in children {
+            res.extend(field(5, c));
+        }
+        field(1, &res)
+    }
+
+    // the host references string resource 0x7f010000, autoVerify is a compiled boolean primitive
+    let mut host = field(2, b"host");
+    host.extend(field(3, b"@string/host"));
+    host.extend(field(6, &field(1, &[0x10, 0x80, 0x80, 0x84, 0xf8, 0x07])));
+    let mut auto_verify = attribute("autoVerify", "true");
+    auto_verify.extend(field(6, &field(7, &[0x40, 0x01])));
+
+    let intent_filter = element(
+        "intent-filter",
+        &[auto_verify],
+        &[
+            element("action", &[attribute("name", "android.intent.action.VIEW")], &[]),
+            element("category", &[attribute("name", "android.intent.category.BROWSABLE")], &[]),
+            element("data", &[attribute("scheme", "https"), host], &[]),
+        ],
+    );
+    let activity = element("activity", &[attribute("name", ".MainActivity")], &[intent_filter]);
+    let application = element("application", &[], &[activity]);
+    let manifest = element("manifest", &[attribute("package", "com.example")], &[application]);
+
+    // the host differs for the german locale
+    let string_value = |value: &str| {
+        let string = field(1, value.as_bytes());
+        field(2, &field(4, &field(2, &string)))
+    };
+    let mut default_value = field(1, &[]);
+    default_value.extend(string_value("example.com"));
+    let mut german_value = field(1, &field(3, b"de"));
+    german_value.extend(string_value("example.de"));
+    let id = |id: u8| field(1, &[0x08, id]);
+    let mut entry = id(0);
+    entry.extend(field(6, &default_value));
+    entry.extend(field(6, &german_value));
+    let mut resource_type = id(1);
+    resource_type.extend(field(3, &entry));
+    let mut package = id(0x7f);
+    package.extend(field(3, &resource_type));
+    let table = field(2, &package);
+
+    let mut resources = ResourceTable::default();
+    add_proto_table(&mut resources, &table).unwrap();
+
+    let parsed = parse_proto_manifest(&manifest, &resources).unwrap();
+    assert!(parsed.has_auto_verify());
+    let authorities = parsed.unique_authorities();
+    assert_eq!(2, authorities.len());
+    assert_eq!("example.com", authorities[0].to_string());
+    assert_eq!("example.de", authorities[1].to_string());
+
+    // a reference that is not in the resource table is an error instead of a panic
+    assert!(parse_proto_manifest(&manifest, &ResourceTable::default()).is_err());
+}
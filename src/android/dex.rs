@@ -0,0 +1,154 @@
+use std::io::{self, Read, Seek};
+use zip::ZipArchive;
+
+// the names of the classes defined in dex files. only the header, the string, type and class def tables are read.
+
+const HEADER_SIZE: usize = 0x70;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid dex file: {}", msg))
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> io::Result<u32> {
+    match buf.get(offset..offset + 4) {
+        Some(b) => Ok(u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16 | u32::from(b[3]) << 24),
+        None => Err(invalid("offset out of range")),
+    }
+}
+
+// the offset and size of a table of fixed size items, checked against the file size
+fn table(buf: &[u8], header_offset: usize, item_size: usize) -> io::Result<(usize, usize)> {
+    let size = read_u32(buf, header_offset)? as usize;
+    let offset = read_u32(buf, header_offset + 4)? as usize;
+    match size.checked_mul(item_size).and_then(|len| len.checked_add(offset)) {
+        Some(end) if end <= buf.len() => Ok((offset, size)),
+        _ => Err(invalid("table out of range")),
+    }
+}
+
+// string data is the utf-16 length as uleb128 followed by null terminated MUTF-8
+fn read_string(buf: &[u8], offset: usize) -> io::Result<String> {
+    let mut pos = offset;
+    loop {
+        match buf.get(pos) {
+            Some(b) if b & 0x80 != 0 => pos += 1,
+            Some(_) => break,
+            None => return Err(invalid("string out of range")),
+        }
+    }
+    let start = pos + 1;
+    let len = match buf.get(start..).and_then(|b| b.iter().position(|&c| c == 0)) {
+        Some(len) => len,
+        None => return Err(invalid("unterminated string")),
+    };
+    // class names are ascii in practice, MUTF-8 only differs from UTF-8 for NUL and supplementary characters
+    Ok(String::from_utf8_lossy(&buf[start..start + len]).into_owned())
+}
+
+// Lcom/example/Main$Inner; -> com.example.Main$Inner
+fn class_name_from_descriptor(descriptor: &str) -> Option<String> {
+    if descriptor.len() > 2 && descriptor.starts_with('L') && descriptor.ends_with(';') {
+        Some(descriptor[1..descriptor.len() - 1].replace('/', "."))
+    } else {
+        None
+    }
+}
+
+pub fn class_names(buf: &[u8]) -> io::Result<Vec<String>> {
+    if buf.len() < HEADER_SIZE || &buf[..4] != b"dex\n" {
+        return Err(invalid("bad magic"));
+    }
+    let (string_ids_off, string_ids_size) = table(buf, 0x38, 4)?;
+    let (type_ids_off, type_ids_size) = table(buf, 0x40, 4)?;
+    let (class_defs_off, class_defs_size) = table(buf, 0x60, 0x20)?;
+
+    let mut res = Vec::with_capacity(class_defs_size);
+    for i in 0..class_defs_size {
+        let type_idx = read_u32(buf, class_defs_off + i * 0x20)? as usize;
+        if type_idx >= type_ids_size {
+            return Err(invalid("type index out of range"));
+        }
+        let string_idx = read_u32(buf, type_ids_off + type_idx * 4)? as usize;
+        if string_idx >= string_ids_size {
+            return Err(invalid("string index out of range"));
+        }
+        let string_data_off = read_u32(buf, string_ids_off + string_idx * 4)? as usize;
+        let descriptor = read_string(buf, string_data_off)?;
+        if let Some(name) = class_name_from_descriptor(&descriptor) {
+            res.push(name);
+        }
+    }
+    Ok(res)
+}
+
+// the classes of all classes*.dex files in a directory of a zip, "" for APKs and "<module>/dex/" for bundles
+pub fn class_names_from_zip<R: Read + Seek>(zip: &mut ZipArchive<R>, dir: &str) -> io::Result<Vec<String>> {
+    let mut res = Vec::new();
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        let is_dex = {
+            let name = file.name();
+            name.starts_with(dir)
+                && !name[dir.len()..].contains('/')
+                && name[dir.len()..].starts_with("classes")
+                && name.ends_with(".dex")
+        };
+        if !is_dex {
+            continue;
+        }
+        let mut buf = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut buf)?;
+        let names = class_names(&buf)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", file.name(), e)))?;
+        res.extend(names);
+    }
+    Ok(res)
+}
+
+#[test]
+fn test_class_names() {
+    fn u32_bytes(v: usize) -> Vec<u8> {
+        (0..4).map(|i| (v >> (i * 8)) as u8).collect()
+    }
+
+    let descriptors = ["Lcom/example/Main;", "Lcom/example/Main$Inner;", "I"];
+    let mut buf = vec![0u8; HEADER_SIZE];
+    buf[..8].copy_from_slice(b"dex\n035\0");
+
+    let mut string_data_offsets = Vec::new();
+    for descriptor in &descriptors {
+        string_data_offsets.push(buf.len());
+        buf.push(descriptor.len() as u8);
+        buf.extend_from_slice(descriptor.as_bytes());
+        buf.push(0);
+    }
+    let string_ids_off = buf.len();
+    for offset in &string_data_offsets {
+        buf.extend(u32_bytes(*offset));
+    }
+    // the types are in reverse order of the strings
+    let type_ids_off = buf.len();
+    for i in (0..descriptors.len()).rev() {
+        buf.extend(u32_bytes(i));
+    }
+    let class_defs_off = buf.len();
+    for type_idx in &[2, 1] {
+        buf.extend(u32_bytes(*type_idx));
+        buf.extend(vec![0u8; 0x1c]);
+    }
+
+    for (header_offset, size, offset) in &[
+        (0x38, descriptors.len(), string_ids_off),
+        (0x40, descriptors.len(), type_ids_off),
+        (0x60, 2, class_defs_off),
+    ] {
+        buf[*header_offset..*header_offset + 4].copy_from_slice(&u32_bytes(*size));
+        buf[*header_offset + 4..*header_offset + 8].copy_from_slice(&u32_bytes(*offset));
+    }
+
+    assert_eq!(vec!["com.example.Main", "com.example.Main$Inner"], class_names(&buf).unwrap());
+
+    let len = buf.len();
+    assert!(class_names(&buf[..len - 8]).is_err());
+    assert!(class_names(b"not a dex file").is_err());
+}
@@ -0,0 +1,128 @@
+// a flat list of every deep link an app handles, one row per scheme, host and path rule
+
+#[derive(Debug, Serialize)]
+pub struct DeepLink {
+    pub activity: String,
+    // the activity-alias the filter is declared on, links of an alias start the target activity
+    pub alias: Option<String>,
+    pub scheme: String,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub path_rule: Option<String>,
+    pub auto_verify: bool,
+}
+
+const HEADER: [&str; 7] = ["activity", "alias", "scheme", "host", "port", "path rule", "autoVerify"];
+
+impl DeepLink {
+    fn columns(&self) -> Vec<String> {
+        vec![
+            self.activity.clone(),
+            self.alias.clone().unwrap_or_default(),
+            self.scheme.clone(),
+            self.host.clone().unwrap_or_else(|| "*".to_string()),
+            self.port.map(|p| p.to_string()).unwrap_or_default(),
+            self.path_rule.clone().unwrap_or_else(|| "*".to_string()),
+            self.auto_verify.to_string(),
+        ]
+    }
+}
+
+pub fn to_human(links: &[DeepLink]) -> String {
+    let mut rows = vec![HEADER.iter().map(|h| h.to_string()).collect::<Vec<String>>()];
+    rows.extend(links.iter().map(|l| l.columns()));
+
+    let mut widths = vec![0; HEADER.len()];
+    for row in &rows {
+        for (i, column) in row.iter().enumerate() {
+            widths[i] = widths[i].max(column.chars().count());
+        }
+    }
+
+    let mut res = String::new();
+    for row in &rows {
+        let columns: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(column, &width)| format!("{:width$}", column, width = width))
+            .collect();
+        res.push_str(columns.join("  ").trim_end());
+        res.push('\n');
+    }
+    res
+}
+
+pub fn to_json(links: &[DeepLink]) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(links)
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// RFC 4180, wildcards are left empty
+pub fn to_csv(links: &[DeepLink]) -> String {
+    let mut res = HEADER.join(",");
+    res.push_str("\r\n");
+    for link in links {
+        let columns = [
+            link.activity.clone(),
+            link.alias.clone().unwrap_or_default(),
+            link.scheme.clone(),
+            link.host.clone().unwrap_or_default(),
+            link.port.map(|p| p.to_string()).unwrap_or_default(),
+            link.path_rule.clone().unwrap_or_default(),
+            link.auto_verify.to_string(),
+        ];
+        let columns: Vec<String> = columns.iter().map(|c| csv_field(c)).collect();
+        res.push_str(&columns.join(","));
+        res.push_str("\r\n");
+    }
+    res
+}
+
+#[test]
+fn test_formats() {
+    let links = vec![
+        DeepLink {
+            activity: ".Main".to_string(),
+            alias: Some(".Links".to_string()),
+            scheme: "https".to_string(),
+            host: Some("example.com".to_string()),
+            port: None,
+            path_rule: Some("pathPrefix=\"/a,b\"".to_string()),
+            auto_verify: true,
+        },
+        DeepLink {
+            activity: ".Main".to_string(),
+            alias: None,
+            scheme: "myapp".to_string(),
+            host: None,
+            port: None,
+            path_rule: None,
+            auto_verify: false,
+        },
+    ];
+
+    assert_eq!(
+        "activity,alias,scheme,host,port,path rule,autoVerify\r\n\
+         .Main,.Links,https,example.com,,\"pathPrefix=\"\"/a,b\"\"\",true\r\n\
+         .Main,,myapp,,,,false\r\n",
+        to_csv(&links)
+    );
+
+    let human = to_human(&links);
+    let lines: Vec<&str> = human.lines().collect();
+    assert_eq!(3, lines.len());
+    assert!(lines[0].starts_with("activity  alias   scheme  host         port"));
+    assert_eq!(".Main             myapp   *                  *                  false", lines[2]);
+
+    let json: serde_json::Value = serde_json::from_str(&to_json(&links).unwrap()).unwrap();
+    assert_eq!("example.com", json[0]["host"]);
+    assert_eq!(".Links", json[0]["alias"]);
+    assert!(json[1]["host"].is_null());
+}
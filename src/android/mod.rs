@@ -1,14 +1,25 @@
 use clap::ArgMatches;
 use http::Uri;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::process;
 mod apk;
+mod bundle;
+mod dex;
+mod inventory;
+mod protobuf;
+mod resources;
+mod signing;
+mod source;
 use apk_rs::apk::Apk;
 
 pub mod assetlinks;
 
 pub fn run(matches: &ArgMatches) {
-    let url = matches.value_of("URL").unwrap();
-    let url: Uri = url.parse().expect("invalid url");
+    let raw_url = matches.value_of("URL").unwrap();
+    let url: Uri = raw_url.parse().expect("invalid url");
+    // http::Uri does not keep the fragment, intent filters can match against it though
+    let fragment = raw_url.find('#').map(|i| &raw_url[i + 1..]);
 
     if url.host().is_none() {
         panic!("URL must contain a host");
@@ -16,7 +27,59 @@ pub fn run(matches: &ArgMatches) {
 
     let app_id = matches.value_of("app-id").unwrap();
 
-    let assetlinks_uri = assetlinks::assetlinks_json_from_url(&url);
+    let apk_res = check_input(matches);
+
+    if let Some(ref apk_res) = apk_res {
+        for problem in apk_res.check_app_id(app_id) {
+            println!("{}", problem.to_string_human());
+        }
+        if let Some(fingerprint) = apk_res.sha256_fingerprint_string() {
+            println!("Signing certificate SHA-256 fingerprint: {}", fingerprint);
+        }
+        if matches.is_present("data-combinations") {
+            if let Some(ref manifest) = apk_res.manifest {
+                for (activity, combinations) in manifest.data_combinations() {
+                    println!("intent-filter of activity '{}' accepts:", activity);
+                    for combination in combinations {
+                        let note = if combination.intended { "" } else { " (unintended)" };
+                        println!("  {}{}", combination, note);
+                    }
+                }
+            }
+        }
+        if matches.is_present("verification-plan") {
+            if let Some(ref manifest) = apk_res.manifest {
+                println!("hosts verified on install:");
+                for (authority, host) in manifest.verification_plan() {
+                    match host {
+                        Some(host) => println!("  {} -> {}", authority, assetlinks::assetlinks_json_for_host(&host)),
+                        None => println!("  {} -> cannot be verified", authority),
+                    }
+                }
+            }
+        }
+        if let Some(resolution) = apk_res.resolve(&url, fragment) {
+            for m in &resolution.receivers {
+                println!("{}", m.to_string_human());
+            }
+            for (m, exclusion) in &resolution.excluded {
+                println!(
+                    "Activity '{}' accepts the URL but does not receive it: {}.",
+                    m.component_name(),
+                    exclusion.to_string_human()
+                );
+            }
+        }
+        for problem in apk_res.get_problems(&url, fragment) {
+            println!("{}", problem.to_string_human());
+        }
+    }
+
+    // wildcard filters are verified against the assetlinks.json of the base domain
+    let assetlinks_uri = match apk_res.as_ref().and_then(|r| r.verification_host(&url, fragment)) {
+        Some(host) => assetlinks::assetlinks_json_for_host(&host),
+        None => assetlinks::assetlinks_json_from_url(&url),
+    };
     let mut assetlinks_res = assetlinks::fetch_and_check(assetlinks_uri, app_id.into());
 
     if assetlinks_res.is_err() {
@@ -24,11 +87,133 @@ pub fn run(matches: &ArgMatches) {
         process::exit(-1);
     }
 
-    if let Some(apk_file) = matches.value_of("apk") {
-        apk::check_apk(apk_file);
+    let assetlinks = assetlinks_res.unwrap();
+
+    let manifest_package = apk_res
+        .as_ref()
+        .and_then(|r| r.manifest.as_ref())
+        .and_then(|m| m.app_id());
+    // the same problem as AppIdNotInAssetlinks when the manifest package is the app id
+    let mut package_not_listed = false;
+    if let Some(package) = manifest_package {
+        let statements = assetlinks.statements_for_package(package);
+        if !statements.is_empty() {
+            println!("assetlinks.json statements for package '{}':", package);
+            for statement in statements {
+                println!("  {}", statement.to_string_human());
+            }
+        }
+        let problems = assetlinks.check_package(package);
+        package_not_listed = package == app_id && !problems.is_empty();
+        for problem in problems {
+            println!("{}", problem.to_string_human());
+        }
+    }
+
+    let fingerprint = apk_res.as_ref().and_then(|r| r.sha256_fingerprint_string());
+    let package = manifest_package.unwrap_or(app_id);
+    if matches.is_present("login-creds") {
+        for problem in assetlinks.check_login_creds(package, fingerprint.as_ref().map(|f| &f[..])) {
+            println!("{}", problem.to_string_human());
+        }
     }
 
-    let assetlinks = assetlinks_res.unwrap();
+    if let Some(ref apk_res) = apk_res {
+        if matches.is_present("login-creds") || matches.is_present("asset-statements") {
+            let (statements, problems) = apk_res.app_statements(&mut assetlinks::fetch_statements);
+            for problem in problems {
+                println!("{}", problem.to_string_human());
+            }
+            if matches.is_present("asset-statements") {
+                if !statements.is_empty() {
+                    println!("asset_statements of the app:");
+                    for statement in &statements {
+                        println!("  {}", statement.to_string_human());
+                    }
+                }
+                let problems = assetlinks::check_reciprocal(
+                    &statements,
+                    package,
+                    fingerprint.as_ref().map(|f| &f[..]),
+                    &mut assetlinks::fetch_statements,
+                );
+                for problem in problems {
+                    println!("{}", problem.to_string_human());
+                }
+            }
+            if matches.is_present("login-creds") {
+                for problem in assetlinks::check_app_statements(&statements, &assetlinks::site_from_url(&url)) {
+                    println!("{}", problem.to_string_human());
+                }
+            }
+        }
+    }
+
+    let problems: Vec<assetlinks::Problem> = assetlinks.get_problems()
+        .into_iter()
+        .filter(|p| match p {
+            assetlinks::Problem::AppIdNotInAssetlinks => !package_not_listed,
+            _ => true,
+        })
+        .collect();
+    println!("{:#?}", problems);
+}
+
+// the APK, App Bundle, APK set, split APK directory or source manifest given with --apk or --manifest
+fn check_input(matches: &ArgMatches) -> Option<apk::CheckResult> {
+    let checked = if let Some(apk_file) = matches.value_of("apk") {
+        Some((apk_file, apk::check_apk(apk_file)))
+    } else if let Some(manifest_file) = matches.value_of("manifest") {
+        let res_dirs: Vec<PathBuf> = matches
+            .values_of("res")
+            .map(|dirs| dirs.map(PathBuf::from).collect())
+            .unwrap_or_default();
+        let res = apk::check_source_manifest(manifest_file, &placeholders(matches), &res_dirs);
+        Some((manifest_file, res))
+    } else {
+        None
+    };
+
+    match checked {
+        Some((_, Ok(apk_res))) => Some(apk_res),
+        Some((file, Err(e))) => {
+            eprintln!("unable to read {}: {}", file, e);
+            process::exit(-1);
+        }
+        None => None,
+    }
+}
 
-    println!("{:#?}", assetlinks.get_problems());
+// the NAME=VALUE pairs given with --placeholder
+fn placeholders(matches: &ArgMatches) -> HashMap<String, String> {
+    let mut res = HashMap::new();
+    for placeholder in matches.values_of("placeholder").into_iter().flatten() {
+        match placeholder.find('=') {
+            Some(i) => {
+                res.insert(placeholder[..i].to_string(), placeholder[i + 1..].to_string());
+            }
+            None => {
+                eprintln!("invalid placeholder '{}', expected NAME=VALUE", placeholder);
+                process::exit(-1);
+            }
+        }
+    }
+    res
+}
+
+pub fn list_deep_links(matches: &ArgMatches) {
+    let manifest = check_input(matches).and_then(|apk_res| apk_res.manifest);
+    let links = manifest.map(|m| m.deep_links()).unwrap_or_default();
+
+    match matches.value_of("format") {
+        Some("json") => match inventory::to_json(&links) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("unable to serialize deep links: {}", e);
+                process::exit(-1);
+            }
+        },
+        Some("csv") => print!("{}", inventory::to_csv(&links)),
+        _ => print!("{}", inventory::to_human(&links)),
+    }
 }
@@ -0,0 +1,124 @@
+use std::io;
+
+// minimal reader for the protobuf wire format, just enough to walk the aapt2 messages
+// (XmlNode, ResourceTable) contained in Android App Bundles.
+// see: https://developers.google.com/protocol-buffers/docs/encoding
+
+#[derive(Debug, Clone, Copy)]
+pub enum Value<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+impl<'a> Value<'a> {
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match *self {
+            Value::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_string(&self) -> Option<String> {
+        self.as_bytes()
+            .map(|b| String::from_utf8_lossy(b).into_owned())
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::Varint(v) | Value::Fixed64(v) => Some(v),
+            Value::Fixed32(v) => Some(v as u64),
+            Value::Bytes(_) => None,
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> io::Result<u64> {
+    let mut res: u64 = 0;
+    let mut shift = 0;
+    loop {
+        if *pos >= buf.len() {
+            return Err(invalid("truncated varint"));
+        }
+        if shift >= 64 {
+            return Err(invalid("varint too long"));
+        }
+        let b = buf[*pos];
+        *pos += 1;
+        res |= ((b & 0x7f) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Ok(res);
+        }
+        shift += 7;
+    }
+}
+
+fn read_fixed(buf: &[u8], pos: &mut usize, len: usize) -> io::Result<u64> {
+    if *pos + len > buf.len() {
+        return Err(invalid("truncated fixed width value"));
+    }
+    let mut res: u64 = 0;
+    for i in 0..len {
+        res |= (buf[*pos + i] as u64) << (8 * i);
+    }
+    *pos += len;
+    Ok(res)
+}
+
+// decodes all fields of a message in wire order. repeated fields show up multiple times.
+pub fn fields(buf: &[u8]) -> io::Result<Vec<(u32, Value)>> {
+    let mut res = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        let key = read_varint(buf, &mut pos)?;
+        let field = (key >> 3) as u32;
+        let value = match key & 0x7 {
+            0 => Value::Varint(read_varint(buf, &mut pos)?),
+            1 => Value::Fixed64(read_fixed(buf, &mut pos, 8)?),
+            2 => {
+                let len = read_varint(buf, &mut pos)? as usize;
+                if pos + len > buf.len() {
+                    return Err(invalid("truncated length delimited field"));
+                }
+                let bytes = &buf[pos..pos + len];
+                pos += len;
+                Value::Bytes(bytes)
+            }
+            5 => Value::Fixed32(read_fixed(buf, &mut pos, 4)? as u32),
+            t => return Err(invalid(&format!("unsupported wire type {}", t))),
+        };
+        res.push((field, value));
+    }
+
+    Ok(res)
+}
+
+pub fn first<'a>(fields: &[(u32, Value<'a>)], field: u32) -> Option<Value<'a>> {
+    fields.iter().find(|f| f.0 == field).map(|f| f.1)
+}
+
+pub fn all<'a>(fields: &[(u32, Value<'a>)], field: u32) -> Vec<Value<'a>> {
+    fields.iter().filter(|f| f.0 == field).map(|f| f.1).collect()
+}
+
+#[test]
+fn test_fields() {
+    // field 1: varint 150, field 2: string "testing", field 3: fixed32 1
+    let buf = [
+        0x08, 0x96, 0x01, 0x12, 0x07, b't', b'e', b's', b't', b'i', b'n', b'g', 0x1d, 0x01, 0x00,
+        0x00, 0x00,
+    ];
+    let parsed = fields(&buf).unwrap();
+    assert_eq!(3, parsed.len());
+    assert_eq!(Some(150), first(&parsed, 1).unwrap().as_u64());
+    assert_eq!(Some("testing".to_string()), first(&parsed, 2).unwrap().as_string());
+    assert_eq!(Some(1), first(&parsed, 3).unwrap().as_u64());
+    assert!(first(&parsed, 4).is_none());
+
+    assert!(fields(&[0x12, 0x07, b't']).is_err());
+}
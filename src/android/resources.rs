@@ -0,0 +1,453 @@
+use std::collections::HashMap;
+use std::io;
+
+// resource values of all configurations, read from the binary resources.arsc of an APK
+// or the protobuf resource tables of an App Bundle.
+// see: https://android.googlesource.com/platform/frameworks/base/+/refs/heads/master/libs/androidfw/include/androidfw/ResourceTypes.h
+
+const RES_STRING_POOL_TYPE: u16 = 0x0001;
+const RES_TABLE_TYPE: u16 = 0x0002;
+const RES_TABLE_PACKAGE_TYPE: u16 = 0x0200;
+const RES_TABLE_TYPE_TYPE: u16 = 0x0201;
+
+const STRING_POOL_UTF8_FLAG: u32 = 1 << 8;
+const TYPE_FLAG_SPARSE: u8 = 0x01;
+const TYPE_FLAG_OFFSET16: u8 = 0x02;
+const ENTRY_FLAG_COMPLEX: u16 = 0x0001;
+const ENTRY_FLAG_COMPACT: u16 = 0x0008;
+
+const TYPE_REFERENCE: u8 = 0x01;
+const TYPE_STRING: u8 = 0x03;
+const TYPE_INT_DEC: u8 = 0x10;
+const TYPE_INT_HEX: u8 = 0x11;
+const TYPE_INT_BOOLEAN: u8 = 0x12;
+
+// references are followed at most this deep, protects against cycles
+const MAX_REFERENCE_DEPTH: usize = 8;
+
+pub const DEFAULT_CONFIG: &str = "default";
+
+// package 0x01 is the framework, 0x00 a shared library resolved at runtime. neither is in the app's table.
+pub fn is_app_resource(id: u32) -> bool {
+    id >> 24 > 0x01
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Reference(u32),
+    Boolean(bool),
+    Int(i32),
+    // styles, arrays, plurals and other values that are not used by the manifest attributes we read
+    Complex,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ResourceTable {
+    // resource id -> (configuration qualifiers, value)
+    entries: HashMap<u32, Vec<(String, Value)>>,
+}
+
+impl ResourceTable {
+    pub fn insert(&mut self, id: u32, config: String, value: Value) {
+        self.entries.entry(id).or_insert_with(Vec::new).push((config, value));
+    }
+
+    // adds the entries of another table after the existing ones
+    pub fn merge(&mut self, other: &ResourceTable) {
+        for (id, values) in &other.entries {
+            self.entries.entry(*id).or_default().extend(values.iter().cloned());
+        }
+    }
+
+    // every value the resource can take, as pairs of configuration and value. references are followed,
+    // the qualifiers of both configurations are combined, references to framework resources are skipped.
+    // fails if the resource is not in the table.
+    pub fn resolve(&self, id: u32) -> io::Result<Vec<(String, String)>> {
+        let mut res = Vec::new();
+        self.resolve_into(id, DEFAULT_CONFIG, 0, &mut res)?;
+        Ok(res)
+    }
+
+    fn resolve_into(&self, id: u32, config: &str, depth: usize, res: &mut Vec<(String, String)>) -> io::Result<()> {
+        let values = match self.entries.get(&id) {
+            Some(values) if depth < MAX_REFERENCE_DEPTH => values,
+            Some(_) => return Err(invalid(&format!("reference loop at resource @0x{:08x}", id))),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("resource @0x{:08x} not found in the resource table", id),
+                ))
+            }
+        };
+
+        for (value_config, value) in values {
+            let config = combine_configs(config, value_config);
+            let value = match value {
+                Value::String(s) => s.clone(),
+                Value::Boolean(b) => b.to_string(),
+                Value::Int(i) => i.to_string(),
+                Value::Reference(r) => {
+                    if is_app_resource(*r) {
+                        self.resolve_into(*r, &config, depth + 1, res)?;
+                    }
+                    continue;
+                }
+                Value::Complex => continue,
+            };
+            if !res.iter().any(|r| r.0 == config && r.1 == value) {
+                res.push((config, value));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add_arsc(&mut self, buf: &[u8]) -> io::Result<()> {
+        let (chunk_type, header_size, size) = chunk_header(buf, 0)?;
+        if chunk_type != RES_TABLE_TYPE {
+            return Err(invalid("not a resource table"));
+        }
+
+        let mut global_strings = Vec::new();
+        for (offset, chunk_type) in chunks(buf, header_size, size)? {
+            match chunk_type {
+                RES_STRING_POOL_TYPE => global_strings = string_pool(buf, offset)?,
+                RES_TABLE_PACKAGE_TYPE => self.add_arsc_package(buf, offset, &global_strings)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn add_arsc_package(&mut self, buf: &[u8], offset: usize, strings: &[String]) -> io::Result<()> {
+        let (_, header_size, size) = chunk_header(buf, offset)?;
+        let package_id = read_u32(buf, offset + 8)?;
+        for (type_offset, chunk_type) in chunks(buf, offset + header_size, offset + size)? {
+            if chunk_type != RES_TABLE_TYPE_TYPE {
+                continue;
+            }
+            let (_, type_header_size, type_size) = chunk_header(buf, type_offset)?;
+            let type_id = u32::from(read_u8(buf, type_offset + 8)?);
+            let flags = read_u8(buf, type_offset + 9)?;
+            let entry_count = read_u32(buf, type_offset + 12)? as usize;
+            let entries_start = type_offset + read_u32(buf, type_offset + 16)? as usize;
+            let config_size = read_u32(buf, type_offset + 20)? as usize;
+            let config = describe_config(slice(buf, type_offset + 20, config_size)?);
+
+            let index_start = type_offset + type_header_size;
+            let mut entries = Vec::new();
+            for i in 0..entry_count {
+                if flags & TYPE_FLAG_SPARSE != 0 {
+                    let index = read_u16(buf, index_start + i * 4)? as u32;
+                    let entry_offset = read_u16(buf, index_start + i * 4 + 2)? as usize * 4;
+                    entries.push((index, entry_offset));
+                } else if flags & TYPE_FLAG_OFFSET16 != 0 {
+                    let entry_offset = read_u16(buf, index_start + i * 2)?;
+                    if entry_offset != 0xffff {
+                        entries.push((i as u32, entry_offset as usize * 4));
+                    }
+                } else {
+                    let entry_offset = read_u32(buf, index_start + i * 4)?;
+                    if entry_offset != 0xffff_ffff {
+                        entries.push((i as u32, entry_offset as usize));
+                    }
+                }
+            }
+
+            for (index, entry_offset) in entries {
+                let entry = entries_start + entry_offset;
+                if entry >= type_offset + type_size {
+                    return Err(invalid("resource entry outside of its type chunk"));
+                }
+                let entry_flags = read_u16(buf, entry + 2)?;
+                let value = if entry_flags & ENTRY_FLAG_COMPACT != 0 {
+                    let data_type = (entry_flags >> 8) as u8;
+                    arsc_value(data_type, read_u32(buf, entry + 4)?, strings)
+                } else if entry_flags & ENTRY_FLAG_COMPLEX != 0 {
+                    Value::Complex
+                } else {
+                    let entry_size = read_u16(buf, entry)? as usize;
+                    // Res_value: size, res0, dataType, data
+                    let data_type = read_u8(buf, entry + entry_size + 3)?;
+                    arsc_value(data_type, read_u32(buf, entry + entry_size + 4)?, strings)
+                };
+                let id = package_id << 24 | type_id << 16 | index;
+                self.insert(id, config.clone(), value);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn combine_configs(outer: &str, inner: &str) -> String {
+    match (outer, inner) {
+        (DEFAULT_CONFIG, inner) => inner.to_string(),
+        (outer, DEFAULT_CONFIG) => outer.to_string(),
+        (outer, inner) => format!("{}-{}", outer, inner),
+    }
+}
+
+fn arsc_value(data_type: u8, data: u32, strings: &[String]) -> Value {
+    match data_type {
+        TYPE_STRING => strings
+            .get(data as usize)
+            .map(|s| Value::String(s.clone()))
+            .unwrap_or(Value::Complex),
+        TYPE_REFERENCE => Value::Reference(data),
+        TYPE_INT_BOOLEAN => Value::Boolean(data != 0),
+        TYPE_INT_DEC | TYPE_INT_HEX => Value::Int(data as i32),
+        _ => Value::Complex,
+    }
+}
+
+// the qualifiers of a ResTable_config as they appear in resource directory names, e.g. "de-rAT-v21".
+// only the qualifiers that plausibly vary a host or path are named.
+fn describe_config(config: &[u8]) -> String {
+    let u16_at = |offset: usize| read_u16(config, offset).unwrap_or(0);
+    let mut qualifiers = Vec::new();
+
+    if u16_at(4) != 0 {
+        qualifiers.push(format!("mcc{}", u16_at(4)));
+    }
+    if u16_at(6) != 0 {
+        qualifiers.push(format!("mnc{}", u16_at(6)));
+    }
+    if let Some(language) = config.get(8..10).and_then(unpack_locale_part) {
+        qualifiers.push(language);
+    }
+    if let Some(country) = config.get(10..12).and_then(unpack_locale_part) {
+        qualifiers.push(format!("r{}", country.to_uppercase()));
+    }
+    if config.get(29).map(|ui_mode| ui_mode & 0x30 == 0x20).unwrap_or(false) {
+        qualifiers.push("night".to_string());
+    }
+    match u16_at(14) {
+        0 => {}
+        120 => qualifiers.push("ldpi".to_string()),
+        160 => qualifiers.push("mdpi".to_string()),
+        240 => qualifiers.push("hdpi".to_string()),
+        320 => qualifiers.push("xhdpi".to_string()),
+        480 => qualifiers.push("xxhdpi".to_string()),
+        640 => qualifiers.push("xxxhdpi".to_string()),
+        0xfffe => qualifiers.push("anydpi".to_string()),
+        0xffff => qualifiers.push("nodpi".to_string()),
+        density => qualifiers.push(format!("{}dpi", density)),
+    }
+    if u16_at(24) != 0 {
+        qualifiers.push(format!("v{}", u16_at(24)));
+    }
+
+    if !qualifiers.is_empty() {
+        qualifiers.join("-")
+    } else if config.len() > 4 && config[4..].iter().any(|&b| b != 0) {
+        "other".to_string()
+    } else {
+        DEFAULT_CONFIG.to_string()
+    }
+}
+
+// two ascii letters, or three letters packed into two bytes
+fn unpack_locale_part(part: &[u8]) -> Option<String> {
+    if part[0] == 0 {
+        return None;
+    }
+    if part[0] & 0x80 == 0 {
+        return Some(String::from_utf8_lossy(part).into_owned());
+    }
+    let letters = [
+        part[1] & 0x1f,
+        ((part[1] & 0xe0) >> 5) | ((part[0] & 0x03) << 3),
+        (part[0] & 0x7c) >> 2,
+    ];
+    Some(letters.iter().map(|&l| (b'a' + l) as char).collect())
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn slice(buf: &[u8], offset: usize, len: usize) -> io::Result<&[u8]> {
+    buf.get(offset..offset + len)
+        .ok_or_else(|| invalid("unexpected end of resource table"))
+}
+
+fn read_u8(buf: &[u8], offset: usize) -> io::Result<u8> {
+    Ok(slice(buf, offset, 1)?[0])
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> io::Result<u16> {
+    let b = slice(buf, offset, 2)?;
+    Ok(u16::from(b[0]) | u16::from(b[1]) << 8)
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> io::Result<u32> {
+    Ok(u32::from(read_u16(buf, offset)?) | u32::from(read_u16(buf, offset + 2)?) << 16)
+}
+
+// type, header size and size of the chunk at offset
+fn chunk_header(buf: &[u8], offset: usize) -> io::Result<(u16, usize, usize)> {
+    let chunk_type = read_u16(buf, offset)?;
+    let header_size = read_u16(buf, offset + 2)? as usize;
+    let size = read_u32(buf, offset + 4)? as usize;
+    if header_size < 8 || size < header_size || offset + size > buf.len() {
+        return Err(invalid("invalid resource chunk"));
+    }
+    Ok((chunk_type, header_size, size))
+}
+
+// offsets and types of the chunks between start and end
+fn chunks(buf: &[u8], start: usize, end: usize) -> io::Result<Vec<(usize, u16)>> {
+    let mut res = Vec::new();
+    let mut offset = start;
+    while offset < end {
+        let (chunk_type, _, size) = chunk_header(buf, offset)?;
+        res.push((offset, chunk_type));
+        offset += size;
+    }
+    Ok(res)
+}
+
+// one or two bytes, the high bit of the first byte marks the two byte form
+fn read_utf8_length(buf: &[u8], pos: &mut usize) -> io::Result<usize> {
+    let first = read_u8(buf, *pos)? as usize;
+    *pos += 1;
+    if first & 0x80 == 0 {
+        return Ok(first);
+    }
+    let second = read_u8(buf, *pos)? as usize;
+    *pos += 1;
+    Ok((first & 0x7f) << 8 | second)
+}
+
+fn string_pool(buf: &[u8], offset: usize) -> io::Result<Vec<String>> {
+    let (_, header_size, _) = chunk_header(buf, offset)?;
+    let count = read_u32(buf, offset + 8)? as usize;
+    let flags = read_u32(buf, offset + 16)?;
+    let strings_start = offset + read_u32(buf, offset + 20)? as usize;
+
+    let mut res = Vec::with_capacity(count);
+    for i in 0..count {
+        let mut pos = strings_start + read_u32(buf, offset + header_size + i * 4)? as usize;
+        if flags & STRING_POOL_UTF8_FLAG != 0 {
+            // the utf-16 length precedes the utf-8 length
+            read_utf8_length(buf, &mut pos)?;
+            let len = read_utf8_length(buf, &mut pos)?;
+            res.push(String::from_utf8_lossy(slice(buf, pos, len)?).into_owned());
+        } else {
+            let mut len = read_u16(buf, pos)? as usize;
+            pos += 2;
+            if len & 0x8000 != 0 {
+                len = (len & 0x7fff) << 16 | read_u16(buf, pos)? as usize;
+                pos += 2;
+            }
+            let units: Vec<u16> = (0..len)
+                .map(|j| read_u16(buf, pos + j * 2))
+                .collect::<io::Result<_>>()?;
+            res.push(String::from_utf16_lossy(&units));
+        }
+    }
+    Ok(res)
+}
+
+#[test]
+fn test_resolve() {
+    let mut table = ResourceTable::default();
+    table.insert(0x7f02_0000, DEFAULT_CONFIG.to_string(), Value::String("example.com".to_string()));
+    table.insert(0x7f02_0000, "de".to_string(), Value::String("example.de".to_string()));
+    table.insert(0x7f02_0001, DEFAULT_CONFIG.to_string(), Value::Reference(0x7f02_0000));
+    table.insert(0x7f02_0001, "v21".to_string(), Value::Reference(0x7f02_0000));
+    table.insert(0x7f02_0002, DEFAULT_CONFIG.to_string(), Value::Reference(0x7f02_0002));
+    table.insert(0x7f02_0004, DEFAULT_CONFIG.to_string(), Value::String("example.com".to_string()));
+    table.insert(0x7f02_0004, "night".to_string(), Value::Reference(0x0104_0001));
+    table.insert(0x7f02_0004, "v31".to_string(), Value::Reference(0x0010_0001));
+
+    assert_eq!(
+        vec![
+            ("default".to_string(), "example.com".to_string()),
+            ("de".to_string(), "example.de".to_string()),
+            ("v21".to_string(), "example.com".to_string()),
+            ("v21-de".to_string(), "example.de".to_string()),
+        ],
+        table.resolve(0x7f02_0001).unwrap()
+    );
+    assert_eq!(io::ErrorKind::NotFound, table.resolve(0x7f02_0003).unwrap_err().kind());
+    assert!(table.resolve(0x7f02_0002).is_err());
+    assert_eq!(
+        vec![("default".to_string(), "example.com".to_string())],
+        table.resolve(0x7f02_0004).unwrap()
+    );
+}
+
+#[test]
+fn test_add_arsc() {
+    fn chunk(chunk_type: u16, header: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut res = Vec::new();
+        res.extend_from_slice(&chunk_type.to_le_bytes());
+        res.extend_from_slice(&(8 + header.len() as u16).to_le_bytes());
+        res.extend_from_slice(&(8 + header.len() as u32 + body.len() as u32).to_le_bytes());
+        res.extend_from_slice(header);
+        res.extend_from_slice(body);
+        res
+    }
+    fn type_chunk(language: &[u8; 2], values: &[(u8, u32)]) -> Vec<u8> {
+        let mut config = vec![0u8; 64];
+        config[..4].copy_from_slice(&64u32.to_le_bytes());
+        config[8..10].copy_from_slice(language);
+        let mut header = vec![2, 0, 0, 0];
+        header.extend_from_slice(&(values.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(8 + 12 + 64 + 4 * values.len() as u32).to_le_bytes());
+        header.extend_from_slice(&config);
+        let mut body = Vec::new();
+        for i in 0..values.len() {
+            body.extend_from_slice(&(16 * i as u32).to_le_bytes());
+        }
+        for &(data_type, data) in values {
+            body.extend_from_slice(&[8, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0, data_type]);
+            body.extend_from_slice(&data.to_le_bytes());
+        }
+        chunk(RES_TABLE_TYPE_TYPE, &header, &body)
+    }
+
+    let mut pool_header = Vec::new();
+    for v in &[2u32, 0, STRING_POOL_UTF8_FLAG, 28 + 8, 0] {
+        pool_header.extend_from_slice(&v.to_le_bytes());
+    }
+    let mut pool_body = Vec::new();
+    pool_body.extend_from_slice(&0u32.to_le_bytes());
+    pool_body.extend_from_slice(&14u32.to_le_bytes());
+    pool_body.extend_from_slice(b"\x0b\x0bexample.com\x00");
+    pool_body.extend_from_slice(b"\x0a\x0aexample.de\x00");
+    let pool = chunk(RES_STRING_POOL_TYPE, &pool_header, &pool_body);
+
+    let mut package_header = vec![0u8; 280];
+    package_header[..4].copy_from_slice(&0x7fu32.to_le_bytes());
+    let mut package_body = type_chunk(&[0, 0], &[(TYPE_STRING, 0), (TYPE_REFERENCE, 0x7f02_0000)]);
+    package_body.extend(type_chunk(b"de", &[(TYPE_STRING, 1)]));
+    let package = chunk(RES_TABLE_PACKAGE_TYPE, &package_header, &package_body);
+
+    let mut table_body = pool;
+    table_body.extend(package);
+    let arsc = chunk(RES_TABLE_TYPE, &1u32.to_le_bytes(), &table_body);
+
+    let mut table = ResourceTable::default();
+    table.add_arsc(&arsc).unwrap();
+    assert_eq!(
+        vec![
+            ("default".to_string(), "example.com".to_string()),
+            ("de".to_string(), "example.de".to_string()),
+        ],
+        table.resolve(0x7f02_0001).unwrap()
+    );
+    assert!(table.add_arsc(&arsc[..20]).is_err());
+}
+
+#[test]
+fn test_describe_config() {
+    let mut config = vec![0u8; 64];
+    assert_eq!("default", describe_config(&config));
+    config[8..12].copy_from_slice(b"deat");
+    config[24] = 21;
+    assert_eq!("de-rAT-v21", describe_config(&config));
+    // "fil" packed into two bytes
+    assert_eq!(Some("fil".to_string()), unpack_locale_part(&[0xad, 0x05]));
+}
@@ -0,0 +1,249 @@
+use crate::der;
+use sha2::{Digest, Sha256};
+use std::io::{self, Read, Seek};
+
+const APK_SIG_BLOCK_MAGIC: &[u8] = b"APK Sig Block 42";
+const APK_SIGNATURE_SCHEME_V2_BLOCK_ID: u32 = 0x7109_871a;
+const APK_SIGNATURE_SCHEME_V3_BLOCK_ID: u32 = 0xf053_68c0;
+const EOCD_MAGIC: [u8; 4] = [0x50, 0x4b, 0x05, 0x06];
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> io::Result<u32> {
+    if offset + 4 > buf.len() {
+        return Err(invalid("unexpected end of signing block"));
+    }
+    Ok(buf[offset] as u32
+        | (buf[offset + 1] as u32) << 8
+        | (buf[offset + 2] as u32) << 16
+        | (buf[offset + 3] as u32) << 24)
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> io::Result<u64> {
+    let low = read_u32(buf, offset)? as u64;
+    let high = read_u32(buf, offset + 4)? as u64;
+    Ok(low | high << 32)
+}
+
+// splits a sequence of u32 length prefixed values
+fn length_prefixed_values(buf: &[u8]) -> io::Result<Vec<&[u8]>> {
+    let mut res = Vec::new();
+    let mut offset = 0;
+    while offset < buf.len() {
+        let len = read_u32(buf, offset)? as usize;
+        offset += 4;
+        if offset + len > buf.len() {
+            return Err(invalid("length prefixed value exceeds its container"));
+        }
+        res.push(&buf[offset..offset + len]);
+        offset += len;
+    }
+    Ok(res)
+}
+
+fn length_prefixed_value(buf: &[u8]) -> io::Result<&[u8]> {
+    let len = read_u32(buf, 0)? as usize;
+    if 4 + len > buf.len() {
+        return Err(invalid("length prefixed value exceeds its container"));
+    }
+    Ok(&buf[4..4 + len])
+}
+
+fn find_apk_signing_block(apk: &[u8]) -> io::Result<Option<&[u8]>> {
+    // the end of central directory record is at least 22 bytes and can be followed by a comment
+    if apk.len() < 22 {
+        return Err(invalid("file too small to be a zip archive"));
+    }
+    let eocd = (0..=apk.len() - 22)
+        .rev()
+        .take(0xffff + 1)
+        .find(|&i| apk[i..i + 4] == EOCD_MAGIC);
+    let eocd = match eocd {
+        Some(eocd) => eocd,
+        None => return Err(invalid("end of central directory not found")),
+    };
+
+    let cd_offset = read_u32(apk, eocd + 16)? as usize;
+    if cd_offset < 32 || cd_offset > apk.len() {
+        return Ok(None);
+    }
+    if &apk[cd_offset - 16..cd_offset] != APK_SIG_BLOCK_MAGIC {
+        return Ok(None);
+    }
+
+    // the size field is repeated at the start and the end of the block and does not include itself
+    // the block ends with the repeated size and the magic, 24 bytes
+    let block_size = read_u64(apk, cd_offset - 24)?;
+    let block_start = match block_size.checked_add(8) {
+        Some(len) if block_size >= 24 && len <= cd_offset as u64 => cd_offset - len as usize,
+        _ => return Err(invalid("invalid APK signing block size")),
+    };
+    Ok(Some(&apk[block_start + 8..cd_offset - 24]))
+}
+
+// certificates of all signers from a v2 or v3 APK signature scheme block
+fn certificates_from_signature_scheme_block(block: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    let mut res = Vec::new();
+    for signer in length_prefixed_values(length_prefixed_value(block)?)? {
+        let signed_data = length_prefixed_value(signer)?;
+        let digests_len = read_u32(signed_data, 0)? as usize;
+        if 4 + digests_len > signed_data.len() {
+            return Err(invalid("invalid signed data"));
+        }
+        let certificates = length_prefixed_value(&signed_data[4 + digests_len..])?;
+        for certificate in length_prefixed_values(certificates)? {
+            res.push(certificate.to_vec());
+        }
+    }
+    Ok(res)
+}
+
+// reads the certificates from the APK signing block (v3 preferred over v2)
+pub fn certificates_from_signing_block(apk: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    let block = match find_apk_signing_block(apk)? {
+        Some(block) => block,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut v2 = None;
+    let mut v3 = None;
+    let mut offset = 0;
+    while offset + 12 <= block.len() {
+        let len = read_u64(block, offset)?;
+        let end = match (offset as u64 + 8).checked_add(len) {
+            Some(end) if len >= 4 && end <= block.len() as u64 => end as usize,
+            _ => return Err(invalid("invalid APK signing block entry")),
+        };
+        let id = read_u32(block, offset + 8)?;
+        let value = &block[offset + 12..end];
+        match id {
+            APK_SIGNATURE_SCHEME_V2_BLOCK_ID => v2 = Some(value),
+            APK_SIGNATURE_SCHEME_V3_BLOCK_ID => v3 = Some(value),
+            _ => {}
+        }
+        offset = end;
+    }
+
+    match v3.or(v2) {
+        Some(value) => certificates_from_signature_scheme_block(value),
+        None => Ok(Vec::new()),
+    }
+}
+
+// extracts the certificates from a PKCS#7 SignedData structure as written by jarsigner (META-INF/*.RSA)
+pub fn certificates_from_pkcs7(buf: &[u8]) -> Result<Vec<Vec<u8>>, der::Error> {
+    let (content_info, _) = der::parse(buf)?;
+    let content_info = content_info.children()?;
+    let signed_data = content_info
+        .iter()
+        .find(|c| c.tag == der::TAG_CONTEXT_0)
+        .ok_or(der::Error::Truncated)?;
+    let signed_data = signed_data.children()?;
+    let signed_data = signed_data
+        .iter()
+        .find(|c| c.tag == der::TAG_SEQUENCE)
+        .ok_or(der::Error::Truncated)?;
+
+    let mut res = Vec::new();
+    for field in signed_data.children()? {
+        // certificates [0] IMPLICIT SET OF Certificate
+        if field.tag == der::TAG_CONTEXT_0 {
+            for certificate in field.children()? {
+                res.push(certificate.raw.to_vec());
+            }
+        }
+    }
+    Ok(res)
+}
+
+// reads the certificates of a jar signed archive (v1 scheme), used by App Bundles and old APKs
+pub fn certificates_from_jar_signature<R: Read + Seek>(
+    zip: &mut zip::ZipArchive<R>,
+) -> io::Result<Vec<Vec<u8>>> {
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        let name = file.name().to_string();
+        if !name.starts_with("META-INF/") || name[9..].contains('/') {
+            continue;
+        }
+        if name.ends_with(".RSA") || name.ends_with(".DSA") || name.ends_with(".EC") {
+            let mut buf = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut buf)?;
+            return certificates_from_pkcs7(&buf).map_err(|_| invalid("invalid PKCS#7 signature"));
+        }
+    }
+    Ok(Vec::new())
+}
+
+pub fn sha256_fingerprint(certificate: &[u8]) -> Vec<u8> {
+    Sha256::digest(certificate).to_vec()
+}
+
+// formats a fingerprint the way assetlinks.json expects it
+pub fn fingerprint_to_string(fingerprint: &[u8]) -> String {
+    let bytes: Vec<String> = fingerprint.iter().map(|b| format!("{:02X}", b)).collect();
+    bytes.join(":")
+}
+
+#[test]
+fn test_fingerprint_to_string() {
+    assert_eq!("0A:FF:10", fingerprint_to_string(&[0x0a, 0xff, 0x10]));
+}
+
+#[test]
+fn test_certificates_from_signing_block() {
+    let certificate = vec![0x30, 0x03, 0x02, 0x01, 0x01];
+
+    // v2 block: signers -> signer -> signed data -> (digests, certificates)
+    let mut certificates = Vec::new();
+    certificates.extend_from_slice(&(certificate.len() as u32).to_le_bytes());
+    certificates.extend_from_slice(&certificate);
+    let mut signed_data = Vec::new();
+    signed_data.extend_from_slice(&0u32.to_le_bytes());
+    signed_data.extend_from_slice(&(certificates.len() as u32).to_le_bytes());
+    signed_data.extend_from_slice(&certificates);
+    let mut signer = Vec::new();
+    signer.extend_from_slice(&(signed_data.len() as u32).to_le_bytes());
+    signer.extend_from_slice(&signed_data);
+    let mut signers = Vec::new();
+    signers.extend_from_slice(&(signer.len() as u32).to_le_bytes());
+    signers.extend_from_slice(&signer);
+    let mut v2 = Vec::new();
+    v2.extend_from_slice(&(signers.len() as u32).to_le_bytes());
+    v2.extend_from_slice(&signers);
+
+    let mut pairs = Vec::new();
+    pairs.extend_from_slice(&(v2.len() as u64 + 4).to_le_bytes());
+    pairs.extend_from_slice(&APK_SIGNATURE_SCHEME_V2_BLOCK_ID.to_le_bytes());
+    pairs.extend_from_slice(&v2);
+
+    let block_size = (pairs.len() + 8 + 16) as u64;
+    let mut apk = vec![0u8; 10]; // zip entries
+    apk.extend_from_slice(&block_size.to_le_bytes());
+    apk.extend_from_slice(&pairs);
+    apk.extend_from_slice(&block_size.to_le_bytes());
+    apk.extend_from_slice(APK_SIG_BLOCK_MAGIC);
+    let cd_offset = apk.len() as u32;
+    apk.extend_from_slice(&EOCD_MAGIC);
+    apk.extend_from_slice(&[0u8; 12]);
+    apk.extend_from_slice(&cd_offset.to_le_bytes());
+    apk.extend_from_slice(&[0u8; 2]);
+
+    assert_eq!(vec![certificate], certificates_from_signing_block(&apk).unwrap());
+    // an apk without signing block
+    assert!(certificates_from_signing_block(&apk[apk.len() - 22..]).unwrap().is_empty());
+    assert!(certificates_from_signing_block(&[0u8; 4]).is_err());
+
+    // garbage block and pair sizes
+    let size_offset = cd_offset as usize - 24;
+    for size in &[8u64, u64::MAX] {
+        let mut garbage = apk.clone();
+        garbage[size_offset..size_offset + 8].copy_from_slice(&size.to_le_bytes());
+        assert!(certificates_from_signing_block(&garbage).is_err());
+    }
+    let mut garbage = apk.clone();
+    garbage[18..26].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(certificates_from_signing_block(&garbage).is_err());
+}
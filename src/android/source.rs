@@ -0,0 +1,331 @@
+use crate::android::apk::{self, AttributeValue, Element, Manifest, ManifestEvent};
+use crate::android::resources::{self, ResourceTable};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use xml::reader::{EventReader, XmlEvent};
+
+// reads plain text manifests as found in the source tree (src/main/AndroidManifest.xml) or written by
+// the gradle manifest merger, so the intent filters can be checked before an APK is built.
+
+const ANDROID_NAMESPACE: &str = "http://schemas.android.com/apk/res/android";
+
+// string resources get made up ids in the app package, references between them are resolved by the ResourceTable
+const STRING_TYPE_ID: u32 = 0x7f01_0000;
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn xml_error(path: &Path, e: xml::reader::Error) -> io::Error {
+    invalid(format!("{}: {}", path.display(), e))
+}
+
+// the <string> resources of res/values*/*.xml, indexed by name
+#[derive(Debug, Default)]
+pub struct StringResources {
+    ids: HashMap<String, u32>,
+    table: ResourceTable,
+}
+
+impl StringResources {
+    // reads all values directories of a res directory, the qualifiers of the directory name are the configuration
+    pub fn add_res_dir(&mut self, res_dir: &Path) -> io::Result<()> {
+        let mut values_dirs: Vec<PathBuf> = fs::read_dir(res_dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                path.is_dir() && (name == "values" || name.starts_with("values-"))
+            })
+            .collect();
+        values_dirs.sort();
+
+        for dir in values_dirs {
+            let config = match dir.file_name().and_then(|n| n.to_str()) {
+                Some(name) if name.starts_with("values-") => name[7..].to_string(),
+                _ => resources::DEFAULT_CONFIG.to_string(),
+            };
+            let mut files: Vec<PathBuf> = fs::read_dir(&dir)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().map(|e| e == "xml").unwrap_or(false))
+                .collect();
+            files.sort();
+            for file in files {
+                self.add_values_file(&file, &config)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn add_values_file(&mut self, path: &Path, config: &str) -> io::Result<()> {
+        let reader = EventReader::new(BufReader::new(File::open(path)?));
+        let mut current: Option<(String, String)> = None;
+        for event in reader {
+            match event.map_err(|e| xml_error(path, e))? {
+                XmlEvent::StartElement { name, attributes, .. } => {
+                    if name.local_name == "string" && current.is_none() {
+                        let string_name = attributes
+                            .iter()
+                            .find(|a| a.name.local_name == "name")
+                            .map(|a| a.value.clone());
+                        if let Some(string_name) = string_name {
+                            current = Some((string_name, String::new()));
+                        }
+                    }
+                }
+                XmlEvent::Characters(text) | XmlEvent::CData(text) | XmlEvent::Whitespace(text) => {
+                    if let Some((_, ref mut value)) = current {
+                        value.push_str(&text);
+                    }
+                }
+                XmlEvent::EndElement { name } => {
+                    if name.local_name == "string" {
+                        if let Some((string_name, value)) = current.take() {
+                            self.insert(&string_name, config, &value);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn id(&mut self, name: &str) -> u32 {
+        let next = STRING_TYPE_ID | self.ids.len() as u32;
+        *self.ids.entry(name.to_string()).or_insert(next)
+    }
+
+    pub fn insert(&mut self, name: &str, config: &str, value: &str) {
+        let id = self.id(name);
+        let value = match unescape_string(value) {
+            ref v if v.starts_with("@string/") => resources::Value::Reference(self.id(&v[8..])),
+            v => resources::Value::String(v),
+        };
+        self.table.insert(id, config.to_string(), value);
+    }
+
+    // the values of @string/name in all configurations
+    fn resolve(&self, name: &str) -> io::Result<Option<AttributeValue>> {
+        match self.ids.get(name) {
+            Some(&id) => apk::resolve_reference(id, &self.table),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("string resource '{}' not found", name),
+            )),
+        }
+    }
+}
+
+// applies the quoting and escaping rules of aapt to a string resource
+fn unescape_string(value: &str) -> String {
+    let value = value.trim();
+    let (value, quoted) = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        (&value[1..value.len() - 1], true)
+    } else {
+        (value, false)
+    };
+
+    let mut res = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    let mut last_whitespace = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => res.push('\n'),
+                Some('t') => res.push('\t'),
+                Some(c) => res.push(c),
+                None => {}
+            },
+            // whitespace is collapsed outside of quotes
+            c if c.is_whitespace() && !quoted => {
+                if !last_whitespace {
+                    res.push(' ');
+                }
+                last_whitespace = true;
+                continue;
+            }
+            c => res.push(c),
+        }
+        last_whitespace = false;
+    }
+    res
+}
+
+// replaces ${name} placeholders the way the manifest merger does, unknown placeholders are an error
+fn substitute_placeholders(value: &str, placeholders: &HashMap<String, String>) -> io::Result<String> {
+    let mut res = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let name = &rest[start + 2..end];
+        match placeholders.get(name) {
+            Some(replacement) => {
+                res.push_str(&rest[..start]);
+                res.push_str(replacement);
+            }
+            None => return Err(invalid(format!("no value for placeholder ${{{}}}", name))),
+        }
+        rest = &rest[end + 1..];
+    }
+    res.push_str(rest);
+    Ok(res)
+}
+
+fn resolve_attribute_value(
+    value: &str,
+    placeholders: &HashMap<String, String>,
+    strings: &StringResources,
+) -> io::Result<AttributeValue> {
+    let value = substitute_placeholders(value, placeholders)?;
+    if value.starts_with("@string/") {
+        if let Some(resolved) = strings.resolve(&value[8..])? {
+            return Ok(resolved);
+        }
+    }
+    Ok(AttributeValue::String(value))
+}
+
+// attributes the checks don't read keep their literal value when a placeholder or string is missing,
+// labels often come from libraries whose resources are not available
+fn attribute_value(
+    name: &str,
+    value: &str,
+    placeholders: &HashMap<String, String>,
+    strings: &StringResources,
+) -> io::Result<AttributeValue> {
+    match resolve_attribute_value(value, placeholders, strings) {
+        Err(_) if !apk::is_checked_attribute(name) => Ok(AttributeValue::String(value.to_string())),
+        res => res,
+    }
+}
+
+pub fn parse_source_manifest<R: Read>(
+    rdr: R,
+    placeholders: &HashMap<String, String>,
+    strings: &StringResources,
+) -> io::Result<Manifest> {
+    let mut events = Vec::new();
+    for event in EventReader::new(rdr) {
+        match event.map_err(|e| invalid(e.to_string()))? {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                let mut element_attributes = Vec::new();
+                for a in attributes {
+                    // tools: and other namespaces only instruct the merger
+                    let relevant = match a.name.namespace {
+                        Some(ref namespace) => namespace == ANDROID_NAMESPACE,
+                        None => true,
+                    };
+                    if !relevant {
+                        continue;
+                    }
+                    let value = attribute_value(&a.name.local_name, &a.value, placeholders, strings).map_err(|e| {
+                        io::Error::new(e.kind(), format!("{} (android:{} of <{}>)", e, a.name.local_name, name.local_name))
+                    })?;
+                    element_attributes.push((a.name.local_name, value));
+                }
+
+                // since AGP 7 the package is set in the build script, the applicationId placeholder stands in for it
+                if name.local_name == "manifest" && !element_attributes.iter().any(|a| a.0 == "package") {
+                    if let Some(application_id) = placeholders.get("applicationId") {
+                        element_attributes.push(("package".to_string(), AttributeValue::String(application_id.clone())));
+                    }
+                }
+
+                events.push(ManifestEvent::ElementStart(Element {
+                    name: name.local_name,
+                    attributes: element_attributes,
+                }));
+            }
+            XmlEvent::EndElement { name } => events.push(ManifestEvent::ElementEnd(name.local_name)),
+            _ => {}
+        }
+    }
+    Ok(apk::manifest_from_events(events))
+}
+
+// reads a source or merged manifest. string resources are taken from the given res directories,
+// or from the res directory next to the manifest (src/main/res) if none are given.
+pub fn read_source_manifest(
+    path: &Path,
+    placeholders: &HashMap<String, String>,
+    res_dirs: &[PathBuf],
+) -> io::Result<Manifest> {
+    let mut strings = StringResources::default();
+    let default_res_dir = path.parent().map(|p| p.join("res"));
+    let res_dirs: Vec<&Path> = if res_dirs.is_empty() {
+        default_res_dir.iter().filter(|d| d.is_dir()).map(|d| d.as_path()).collect()
+    } else {
+        res_dirs.iter().map(|d| d.as_path()).collect()
+    };
+    for res_dir in res_dirs {
+        strings.add_res_dir(res_dir)?;
+    }
+
+    let rdr = BufReader::new(File::open(path)?);
+    parse_source_manifest(rdr, placeholders, &strings)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+#[test]
+fn test_unescape_string() {
+    assert_eq!("it's", unescape_string("it\\'s"));
+    assert_eq!("a b", unescape_string("  a \n  b "));
+    assert_eq!("  a  ", unescape_string("\"  a  \""));
+}
+
+#[test]
+fn test_substitute_placeholders() {
+    let mut placeholders = HashMap::new();
+    placeholders.insert("host".to_string(), "example.com".to_string());
+    assert_eq!("example.com", substitute_placeholders("${host}", &placeholders).unwrap());
+    assert_eq!("www.example.com/x", substitute_placeholders("www.${host}/x", &placeholders).unwrap());
+    assert!(substitute_placeholders("${missing}", &placeholders).is_err());
+}
+
+#[test]
+fn test_parse_source_manifest() {
+    let manifest = r#"<?xml version="1.0" encoding="utf-8"?>
+<manifest xmlns:android="http://schemas.android.com/apk/res/android"
+    xmlns:tools="http://schemas.android.com/tools">
+    <application android:label="@string/library_label" android:icon="${appIcon}">
+        <meta-data android:name="asset_statements" android:resource="@string/asset_statements" />
+        <activity android:name=".MainActivity" android:exported="true" tools:node="merge">
+            <intent-filter android:autoVerify="true">
+                <action android:name="android.intent.action.VIEW" />
+                <category android:name="android.intent.category.DEFAULT" />
+                <category android:name="android.intent.category.BROWSABLE" />
+                <data android:scheme="https" android:host="@string/host" />
+                <data android:host="${extraHost}" />
+            </intent-filter>
+        </activity>
+    </application>
+</manifest>"#;
+
+    let mut placeholders = HashMap::new();
+    placeholders.insert("applicationId".to_string(), "com.example".to_string());
+    placeholders.insert("extraHost".to_string(), "links.example.com".to_string());
+    let mut strings = StringResources::default();
+    strings.insert("host", resources::DEFAULT_CONFIG, "@string/production_host");
+    strings.insert("production_host", resources::DEFAULT_CONFIG, "example.com");
+    strings.insert("host", "de", "example.de");
+    strings.insert("asset_statements", resources::DEFAULT_CONFIG, "[{\\\"include\\\": \\\"https://example.com/.well-known/assetlinks.json\\\"}]");
+
+    let parsed = parse_source_manifest(manifest.as_bytes(), &placeholders, &strings).unwrap();
+    assert!(parsed.has_auto_verify());
+    let hosts: Vec<String> = parsed.unique_authorities().iter().map(|a| a.to_string()).collect();
+    assert_eq!(vec!["example.com", "example.de", "links.example.com"], hosts);
+    assert_eq!(
+        Some("[{\"include\": \"https://example.com/.well-known/assetlinks.json\"}]"),
+        parsed.asset_statements()
+    );
+    assert_eq!(1, parsed.deep_links().iter().filter(|l| l.host == Some("example.de".to_string())).count());
+
+    // the unresolved label and icon are tolerated above, the unresolved host is not
+    assert!(parse_source_manifest(manifest.as_bytes(), &HashMap::new(), &strings).is_err());
+    assert!(parse_source_manifest(manifest.as_bytes(), &placeholders, &StringResources::default()).is_err());
+}
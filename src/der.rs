@@ -0,0 +1,157 @@
+// minimal ASN.1 DER/BER reader. it only splits a buffer into tag-length-value elements,
+// interpreting the values is left to the caller.
+// indefinite lengths (BER) are supported because they are common in CMS files produced by apple.
+
+pub const TAG_SEQUENCE: u8 = 0x30;
+pub const TAG_CONTEXT_0: u8 = 0xa0;
+
+// nesting of indefinite length elements, skipping their content recurses once per level
+const MAX_INDEFINITE_DEPTH: usize = 32;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Truncated,
+    UnsupportedTag(u8),
+    UnsupportedLength,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Tlv<'a> {
+    pub tag: u8,
+    pub content: &'a [u8],
+    // the complete encoding including tag and length
+    pub raw: &'a [u8],
+}
+
+impl<'a> Tlv<'a> {
+    pub fn children(&self) -> Result<Vec<Tlv<'a>>, Error> {
+        parse_all(self.content)
+    }
+}
+
+// parses the first element of buf and returns it together with the remaining bytes
+pub fn parse(buf: &[u8]) -> Result<(Tlv, &[u8]), Error> {
+    parse_nested(buf, 0)
+}
+
+fn parse_nested<'a>(buf: &'a [u8], depth: usize) -> Result<(Tlv<'a>, &'a [u8]), Error> {
+    if buf.len() < 2 {
+        return Err(Error::Truncated);
+    }
+    let tag = buf[0];
+    if tag & 0x1f == 0x1f {
+        // multi byte tags are not used in any of the structures we read
+        return Err(Error::UnsupportedTag(tag));
+    }
+
+    let (len, header) = match buf[1] {
+        l if l < 0x80 => (Some(l as usize), 2),
+        0x80 => (None, 2),
+        l => {
+            let n = (l & 0x7f) as usize;
+            if n > 4 {
+                return Err(Error::UnsupportedLength);
+            }
+            if buf.len() < 2 + n {
+                return Err(Error::Truncated);
+            }
+            let len = buf[2..2 + n]
+                .iter()
+                .fold(0usize, |acc, &b| (acc << 8) | b as usize);
+            (Some(len), 2 + n)
+        }
+    };
+
+    match len {
+        Some(len) => {
+            if buf.len() < header + len {
+                return Err(Error::Truncated);
+            }
+            let tlv = Tlv {
+                tag,
+                content: &buf[header..header + len],
+                raw: &buf[..header + len],
+            };
+            Ok((tlv, &buf[header + len..]))
+        }
+        None => {
+            if tag & 0x20 == 0 || depth >= MAX_INDEFINITE_DEPTH {
+                return Err(Error::UnsupportedLength);
+            }
+            // indefinite length, content ends with two zero bytes
+            let mut rest = &buf[header..];
+            loop {
+                if rest.len() >= 2 && rest[0] == 0 && rest[1] == 0 {
+                    let end = buf.len() - rest.len();
+                    let tlv = Tlv {
+                        tag,
+                        content: &buf[header..end],
+                        raw: &buf[..end + 2],
+                    };
+                    return Ok((tlv, &rest[2..]));
+                }
+                let (_, r) = parse_nested(rest, depth + 1)?;
+                rest = r;
+            }
+        }
+    }
+}
+
+pub fn parse_all(mut buf: &[u8]) -> Result<Vec<Tlv>, Error> {
+    let mut res = Vec::new();
+    while !buf.is_empty() {
+        let (tlv, rest) = parse(buf)?;
+        res.push(tlv);
+        buf = rest;
+    }
+    Ok(res)
+}
+
+// a tag-length-value element with a short or two byte long form length, for the tests of the DER readers
+#[cfg(test)]
+pub fn build_tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut res = match content.len() {
+        len if len < 0x80 => vec![tag, len as u8],
+        len => vec![tag, 0x82, (len >> 8) as u8, len as u8],
+    };
+    res.extend_from_slice(content);
+    res
+}
+
+#[test]
+fn test_parse() {
+    // SEQUENCE { INTEGER 5, UTF8String "ab" }
+    let buf = [0x30, 0x07, 0x02, 0x01, 0x05, 0x0c, 0x02, b'a', b'b'];
+    let (seq, rest) = parse(&buf).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(TAG_SEQUENCE, seq.tag);
+    let children = seq.children().unwrap();
+    assert_eq!(2, children.len());
+    assert_eq!(0x02, children[0].tag);
+    assert_eq!(&[0x05], children[0].content);
+    assert_eq!(b"ab", children[1].content);
+    assert_eq!(&buf[5..], children[1].raw);
+
+    // same sequence with indefinite length
+    let buf = [0x30, 0x80, 0x02, 0x01, 0x05, 0x00, 0x00, 0xff];
+    let (seq, rest) = parse(&buf).unwrap();
+    assert_eq!(&[0xff], rest);
+    assert_eq!(1, seq.children().unwrap().len());
+
+    // long form length
+    let mut buf = vec![0x04, 0x81, 0x80];
+    buf.extend_from_slice(&[0u8; 128]);
+    let (s, _) = parse(&buf).unwrap();
+    assert_eq!(128, s.content.len());
+
+    assert_eq!(Error::Truncated, parse(&[0x30, 0x05, 0x00]).unwrap_err());
+
+    let nested = |depth: usize| {
+        let mut buf = [0x30, 0x80].repeat(depth);
+        buf.extend(vec![0x00; 2 * depth]);
+        buf
+    };
+    assert!(parse(&nested(32)).is_ok());
+    assert_eq!(Error::UnsupportedLength, parse(&nested(33)).unwrap_err());
+    assert_eq!(Error::UnsupportedLength, parse(&nested(100_000)).unwrap_err());
+}
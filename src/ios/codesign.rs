@@ -0,0 +1,282 @@
+// parser for the embedded code signature of a Mach-O binary (LC_CODE_SIGNATURE).
+// the signature is a SuperBlob: an index of (slot type, offset) pairs pointing to blobs that
+// each start with their own magic and length. all integers are big endian.
+
+const CSMAGIC_EMBEDDED_SIGNATURE: u32 = 0xfade_0cc0;
+const CSMAGIC_CODEDIRECTORY: u32 = 0xfade_0c02;
+const CSMAGIC_REQUIREMENTS: u32 = 0xfade_0c01;
+const CSMAGIC_EMBEDDED_ENTITLEMENTS: u32 = 0xfade_7171;
+const CSMAGIC_EMBEDDED_DER_ENTITLEMENTS: u32 = 0xfade_7172;
+const CSMAGIC_BLOBWRAPPER: u32 = 0xfade_0b01;
+
+pub const CSSLOT_CODEDIRECTORY: u32 = 0;
+pub const CSSLOT_REQUIREMENTS: u32 = 2;
+pub const CSSLOT_ENTITLEMENTS: u32 = 5;
+pub const CSSLOT_DER_ENTITLEMENTS: u32 = 7;
+pub const CSSLOT_SIGNATURESLOT: u32 = 0x10000;
+
+// code directories with other hash types than the primary one
+const CSSLOT_ALTERNATE_CODEDIRECTORIES: u32 = 0x1000;
+const CSSLOT_ALTERNATE_CODEDIRECTORY_MAX: u32 = 5;
+
+// the version of the code directory that introduced the team id
+const CS_SUPPORTSTEAMID: u32 = 0x20200;
+// code directory flag of ad hoc signatures, made without a certificate
+const CS_ADHOC: u32 = 0x2;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Truncated,
+    // magic of the SuperBlob
+    BadMagic(u32),
+    // slot type of a blob that lies outside of the signature
+    SlotOutOfRange(u32),
+    // slot type and the magic found there
+    UnexpectedMagic(u32, u32),
+    InvalidCodeDirectory,
+}
+
+impl Error {
+    pub fn to_string_human(&self) -> String {
+        match self {
+            Error::Truncated => "The code signature is truncated.".to_string(),
+            Error::BadMagic(magic) => format!("The code signature has an unknown magic 0x{:08x}.", magic),
+            Error::SlotOutOfRange(slot) => format!("Blob 0x{:x} of the code signature lies outside of the signature.", slot),
+            Error::UnexpectedMagic(slot, magic) => {
+                format!("Blob 0x{:x} of the code signature has the wrong magic 0x{:08x}.", slot, magic)
+            }
+            Error::InvalidCodeDirectory => "The code directory of the code signature is invalid.".to_string(),
+        }
+    }
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> Result<u32, Error> {
+    match buf.get(offset..offset + 4) {
+        Some(b) => Ok(u32::from(b[0]) << 24 | u32::from(b[1]) << 16 | u32::from(b[2]) << 8 | u32::from(b[3])),
+        None => Err(Error::Truncated),
+    }
+}
+
+#[derive(Debug)]
+pub struct Blob<'a> {
+    pub slot: u32,
+    // the content without magic and length
+    pub data: &'a [u8],
+}
+
+#[derive(Debug)]
+pub struct CodeSignature<'a> {
+    pub blobs: Vec<Blob<'a>>,
+}
+
+// the magic a blob in the slot is expected to have, None for slots we don't know
+fn expected_magic(slot: u32) -> Option<u32> {
+    match slot {
+        CSSLOT_CODEDIRECTORY => Some(CSMAGIC_CODEDIRECTORY),
+        CSSLOT_REQUIREMENTS => Some(CSMAGIC_REQUIREMENTS),
+        CSSLOT_ENTITLEMENTS => Some(CSMAGIC_EMBEDDED_ENTITLEMENTS),
+        CSSLOT_DER_ENTITLEMENTS => Some(CSMAGIC_EMBEDDED_DER_ENTITLEMENTS),
+        CSSLOT_SIGNATURESLOT => Some(CSMAGIC_BLOBWRAPPER),
+        s if s >= CSSLOT_ALTERNATE_CODEDIRECTORIES
+            && s < CSSLOT_ALTERNATE_CODEDIRECTORIES + CSSLOT_ALTERNATE_CODEDIRECTORY_MAX =>
+        {
+            Some(CSMAGIC_CODEDIRECTORY)
+        }
+        _ => None,
+    }
+}
+
+pub fn parse(buf: &[u8]) -> Result<CodeSignature, Error> {
+    let magic = read_u32(buf, 0)?;
+    if magic != CSMAGIC_EMBEDDED_SIGNATURE {
+        return Err(Error::BadMagic(magic));
+    }
+    // the load command usually covers some padding after the SuperBlob
+    let length = read_u32(buf, 4)? as usize;
+    let buf = buf.get(..length).ok_or(Error::Truncated)?;
+    let count = read_u32(buf, 8)? as usize;
+
+    let mut blobs = Vec::new();
+    for i in 0..count {
+        let slot = read_u32(buf, 12 + i * 8)?;
+        let offset = read_u32(buf, 16 + i * 8)? as usize;
+        let magic = read_u32(buf, offset).map_err(|_| Error::SlotOutOfRange(slot))?;
+        let length = read_u32(buf, offset + 4).map_err(|_| Error::SlotOutOfRange(slot))? as usize;
+        if length < 8 {
+            return Err(Error::Truncated);
+        }
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| buf.get(offset + 8..end))
+            .ok_or(Error::SlotOutOfRange(slot))?;
+        if let Some(expected) = expected_magic(slot) {
+            if magic != expected {
+                return Err(Error::UnexpectedMagic(slot, magic));
+            }
+        }
+        blobs.push(Blob { slot, data });
+    }
+    Ok(CodeSignature { blobs })
+}
+
+#[derive(Debug)]
+pub struct CodeDirectory {
+    pub flags: u32,
+    // the bundle identifier for apps, set by codesign --identifier
+    pub identifier: String,
+    pub team_id: Option<String>,
+}
+
+impl CodeDirectory {
+    pub fn is_ad_hoc(&self) -> bool {
+        self.flags & CS_ADHOC != 0
+    }
+}
+
+// a null terminated string at an offset relative to the start of the blob
+fn read_cstring(data: &[u8], offset: usize) -> Result<String, Error> {
+    // the offsets include the 8 bytes of magic and length that are not part of data
+    let start = offset.checked_sub(8).ok_or(Error::InvalidCodeDirectory)?;
+    let rest = data.get(start..).ok_or(Error::InvalidCodeDirectory)?;
+    let len = rest.iter().position(|&b| b == 0).ok_or(Error::InvalidCodeDirectory)?;
+    Ok(String::from_utf8_lossy(&rest[..len]).into_owned())
+}
+
+impl<'a> CodeSignature<'a> {
+    pub fn blob(&self, slot: u32) -> Option<&'a [u8]> {
+        self.blobs.iter().find(|b| b.slot == slot).map(|b| b.data)
+    }
+
+    // the entitlements as XML plist
+    pub fn entitlements(&self) -> Option<&'a [u8]> {
+        self.blob(CSSLOT_ENTITLEMENTS)
+    }
+
+    // the entitlements as DER, added by Xcode 12 and later
+    pub fn der_entitlements(&self) -> Option<&'a [u8]> {
+        self.blob(CSSLOT_DER_ENTITLEMENTS)
+    }
+
+    // the CMS signature, empty for ad hoc signed binaries
+    pub fn cms_signature(&self) -> Option<&'a [u8]> {
+        self.blob(CSSLOT_SIGNATURESLOT)
+    }
+
+    // ad hoc signed binaries (simulator builds, codesign -s -) have no CMS signature or an empty one
+    pub fn is_ad_hoc(&self) -> Result<bool, Error> {
+        if self.cms_signature().unwrap_or(&[]).is_empty() {
+            return Ok(true);
+        }
+        Ok(match self.code_directory()? {
+            Some(code_directory) => code_directory.is_ad_hoc(),
+            None => false,
+        })
+    }
+
+    pub fn code_directory(&self) -> Result<Option<CodeDirectory>, Error> {
+        let data = match self.blob(CSSLOT_CODEDIRECTORY) {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        let field = |offset: usize| read_u32(data, offset - 8).map_err(|_| Error::InvalidCodeDirectory);
+        let version = field(8)?;
+        let flags = field(12)?;
+        let identifier = read_cstring(data, field(20)? as usize)?;
+        let team_id = match version {
+            v if v >= CS_SUPPORTSTEAMID => match field(48)? {
+                0 => None,
+                offset => Some(read_cstring(data, offset as usize)?),
+            },
+            _ => None,
+        };
+        Ok(Some(CodeDirectory {
+            flags,
+            identifier,
+            team_id,
+        }))
+    }
+}
+
+#[cfg(test)]
+pub fn build_blob(magic: u32, data: &[u8]) -> Vec<u8> {
+    let mut res = Vec::new();
+    res.extend_from_slice(&magic.to_be_bytes());
+    res.extend_from_slice(&(data.len() as u32 + 8).to_be_bytes());
+    res.extend_from_slice(data);
+    res
+}
+
+// a SuperBlob of (slot, blob) pairs, blobs have to include magic and length
+#[cfg(test)]
+pub fn build_super_blob(blobs: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let header_len = 12 + blobs.len() * 8;
+    let mut index = Vec::new();
+    let mut content = Vec::new();
+    for (slot, blob) in blobs {
+        index.extend_from_slice(&slot.to_be_bytes());
+        index.extend_from_slice(&((header_len + content.len()) as u32).to_be_bytes());
+        content.extend_from_slice(blob);
+    }
+    let mut res = Vec::new();
+    res.extend_from_slice(&CSMAGIC_EMBEDDED_SIGNATURE.to_be_bytes());
+    res.extend_from_slice(&((header_len + content.len()) as u32).to_be_bytes());
+    res.extend_from_slice(&(blobs.len() as u32).to_be_bytes());
+    res.extend(index);
+    res.extend(content);
+    res
+}
+
+#[cfg(test)]
+pub fn build_code_directory(identifier: &str, team_id: &str) -> Vec<u8> {
+    let mut data = vec![0u8; 52 - 8];
+    let ident_offset = 52;
+    let team_offset = ident_offset + identifier.len() + 1;
+    data[0..4].copy_from_slice(&CS_SUPPORTSTEAMID.to_be_bytes());
+    data[12..16].copy_from_slice(&(ident_offset as u32).to_be_bytes());
+    data[40..44].copy_from_slice(&(team_offset as u32).to_be_bytes());
+    data.extend_from_slice(identifier.as_bytes());
+    data.push(0);
+    data.extend_from_slice(team_id.as_bytes());
+    data.push(0);
+    build_blob(CSMAGIC_CODEDIRECTORY, &data)
+}
+
+#[test]
+fn test_parse() {
+    // the XML entitlements contain the DER magic and vice versa, scanning for magics would go wrong
+    let xml = b"<plist>\xfa\xde\x71\x72</plist>";
+    let der = b"\x70\x04\xfa\xde\x71\x71";
+    let mut buf = build_super_blob(&[
+        (CSSLOT_CODEDIRECTORY, build_code_directory("com.example.app", "TEAMID1234")),
+        (CSSLOT_REQUIREMENTS, build_blob(CSMAGIC_REQUIREMENTS, &[0, 0, 0, 0])),
+        (CSSLOT_ENTITLEMENTS, build_blob(CSMAGIC_EMBEDDED_ENTITLEMENTS, xml)),
+        (CSSLOT_DER_ENTITLEMENTS, build_blob(CSMAGIC_EMBEDDED_DER_ENTITLEMENTS, der)),
+        (CSSLOT_SIGNATURESLOT, build_blob(CSMAGIC_BLOBWRAPPER, &[])),
+    ]);
+    // padding after the SuperBlob
+    buf.extend_from_slice(&[0; 16]);
+
+    let signature = parse(&buf).unwrap();
+    assert_eq!(5, signature.blobs.len());
+    assert_eq!(Some(&xml[..]), signature.entitlements());
+    assert_eq!(Some(&der[..]), signature.der_entitlements());
+    assert_eq!(Some(&[0u8, 0, 0, 0][..]), signature.blob(CSSLOT_REQUIREMENTS));
+    assert_eq!(Some(&[][..]), signature.cms_signature());
+    let cd = signature.code_directory().unwrap().unwrap();
+    assert_eq!("com.example.app", cd.identifier);
+    assert_eq!(Some("TEAMID1234".to_string()), cd.team_id);
+    assert!(signature.is_ad_hoc().unwrap());
+
+    let len = buf.len();
+    assert_eq!(Error::Truncated, parse(&buf[..len - 20]).unwrap_err());
+    assert_eq!(Error::Truncated, parse(&buf[..10]).unwrap_err());
+    // offset of the last blob points past the end
+    buf[12 + 4 * 8 + 4..12 + 4 * 8 + 8].copy_from_slice(&0xffffu32.to_be_bytes());
+    assert_eq!(Error::SlotOutOfRange(CSSLOT_SIGNATURESLOT), parse(&buf).unwrap_err());
+    assert_eq!(Error::BadMagic(0xfade_7171), parse(&build_blob(CSMAGIC_EMBEDDED_ENTITLEMENTS, xml)).unwrap_err());
+    let wrong = build_super_blob(&[(CSSLOT_ENTITLEMENTS, build_blob(CSMAGIC_EMBEDDED_DER_ENTITLEMENTS, der))]);
+    assert_eq!(
+        Error::UnexpectedMagic(CSSLOT_ENTITLEMENTS, CSMAGIC_EMBEDDED_DER_ENTITLEMENTS),
+        parse(&wrong).unwrap_err()
+    );
+}
@@ -1,31 +1,77 @@
-use mach_object::{LoadCommand, OFile};
+use crate::der;
+use crate::ios::codesign::{self, CodeSignature};
+use mach_object::{cpu_subtype_t, cpu_type_t, LoadCommand, OFile};
 use plist::Plist;
-use std::fs::File;
-use std::io::{Cursor, Read, Write};
-use zip::result::ZipError;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Cursor;
 
 #[derive(Debug)]
 pub struct Entitlements {
     pub application_identifier: Option<String>,
+    pub team_identifier: Option<String>,
     pub associated_domains: Vec<String>,
+    // keys whose values differ between the XML and the DER entitlements
+    pub der_mismatches: Vec<String>,
+    // the architecture the entitlements were taken from and those whose entitlements differ
+    pub architecture: String,
+    pub architecture_mismatches: Vec<String>,
+    // identifier and team of the code directory, not set for .entitlements files
+    pub signing_identifier: Option<String>,
+    pub signing_team_id: Option<String>,
+    pub ad_hoc: bool,
 }
 
 impl Entitlements {
-    fn new() -> Entitlements {
+    pub fn new() -> Entitlements {
         Entitlements {
             application_identifier: None,
+            team_identifier: None,
             associated_domains: Vec::new(),
+            der_mismatches: Vec::new(),
+            architecture: String::new(),
+            architecture_mismatches: Vec::new(),
+            signing_identifier: None,
+            signing_team_id: None,
+            ad_hoc: false,
         }
     }
 
-    pub fn matches_applink_domain(&self, domain: &str) -> bool {
-        for ad in &self.associated_domains {
-            if ad.starts_with("applinks:") && &ad[9..] == domain {
-                return true;
+    fn from_dictionary(dict: &BTreeMap<String, Plist>) -> Entitlements {
+        let mut entitlements = Entitlements::new();
+        if let Some(Plist::String(app_id)) = dict.get("application-identifier") {
+            entitlements.application_identifier = Some(app_id.clone());
+        }
+        if let Some(Plist::String(team_id)) = dict.get("com.apple.developer.team-identifier") {
+            entitlements.team_identifier = Some(team_id.clone());
+        }
+
+        if let Some(Plist::Array(assoc_doms)) = dict.get("com.apple.developer.associated-domains") {
+            for dom in assoc_doms {
+                if let Plist::String(dom) = dom {
+                    entitlements.associated_domains.push(dom.clone());
+                }
             }
         }
+        entitlements
+    }
 
-        false
+    // the applinks entry that covers the domain, see AssociatedDomain::precedence
+    pub fn applink_domain(&self, domain: &str) -> Option<AssociatedDomain> {
+        let mut res: Option<AssociatedDomain> = None;
+        for ad in self.associated_domains.iter().filter_map(|ad| AssociatedDomain::parse(ad)) {
+            if ad.service != "applinks" || !ad.matches(domain) {
+                continue;
+            }
+            let better = match res {
+                Some(ref r) => ad.precedence() > r.precedence(),
+                None => true,
+            };
+            if better {
+                res = Some(ad);
+            }
+        }
+        res
     }
 
     pub fn get_problems(&self, app_id: &str, domain: &str) -> Vec<Problem> {
@@ -35,173 +81,607 @@ impl Entitlements {
                 problems.push(Problem::WrongBundleIdentifier);
             }
         }
-        if !self.matches_applink_domain(domain) {
-            problems.push(Problem::DomainNotInApplinks);
+        match self.applink_domain(domain) {
+            Some(ad) => {
+                if ad.developer {
+                    problems.push(Problem::DeveloperMode(ad.entry.clone()));
+                }
+                if ad.managed {
+                    problems.push(Problem::ManagedMode(ad.entry.clone()));
+                }
+            }
+            None if self.associated_domains.is_empty() => problems.push(Problem::MissingAssociatedDomains),
+            None => {
+                let others: Vec<String> = self
+                    .associated_domains
+                    .iter()
+                    .filter_map(|ad| AssociatedDomain::parse(ad))
+                    .filter(|ad| ad.matches(domain))
+                    .map(|ad| ad.entry)
+                    .collect();
+                if others.is_empty() {
+                    problems.push(Problem::DomainNotInApplinks);
+                } else {
+                    problems.push(Problem::DomainWithoutApplinks(others));
+                }
+            }
+        }
+        problems.extend(self.get_signature_problems());
+        problems
+    }
+
+    // problems of the code signature itself, independent of the link
+    pub fn get_signature_problems(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        if !self.der_mismatches.is_empty() {
+            problems.push(Problem::DerEntitlementsDiffer(self.der_mismatches.clone()));
+        }
+        if !self.architecture_mismatches.is_empty() {
+            problems.push(Problem::ArchitecturesDiffer(
+                self.architecture.clone(),
+                self.architecture_mismatches.clone(),
+            ));
+        }
+        if self.ad_hoc {
+            problems.push(Problem::AdHocSigned);
+        }
+        if let (Some(signing), Some(entitlements)) = (&self.signing_team_id, &self.team_identifier) {
+            if signing != entitlements {
+                problems.push(Problem::SigningTeamMismatch(signing.clone(), entitlements.clone()));
+            }
         }
         problems
     }
 }
 
+#[test]
+fn test_get_problems() {
+    let problems = |domains: &[&str]| {
+        let mut entitlements = Entitlements::new();
+        entitlements.associated_domains = domains.iter().map(|d| d.to_string()).collect();
+        let problems: Vec<String> = entitlements
+            .get_problems("com.example.app", "example.com")
+            .iter()
+            .map(|p| format!("{:?}", p))
+            .collect();
+        problems
+    };
+    assert_eq!(vec!["MissingAssociatedDomains"], problems(&[]));
+    assert_eq!(vec!["DomainNotInApplinks"], problems(&["applinks:example.org"]));
+    assert_eq!(
+        vec!["DomainWithoutApplinks([\"webcredentials:example.com\"])"],
+        problems(&["webcredentials:example.com", "applinks:example.org"])
+    );
+    assert!(problems(&["applinks:example.com"]).is_empty());
+
+    let mut entitlements = Entitlements::new();
+    entitlements.team_identifier = Some("TEAM".to_string());
+    entitlements.signing_team_id = Some("OTHER".to_string());
+    entitlements.ad_hoc = true;
+    let problems: Vec<String> = entitlements.get_signature_problems().iter().map(|p| format!("{:?}", p)).collect();
+    assert_eq!(vec!["AdHocSigned", "SigningTeamMismatch(\"OTHER\", \"TEAM\")"], problems);
+}
+
+// an associated domains entry: <service>:<host>[:<port>][?mode=developer|managed|developer+managed]
+#[derive(Debug, PartialEq)]
+pub struct AssociatedDomain {
+    pub entry: String,
+    pub service: String,
+    // with the port, *.example.com for all subdomains of example.com
+    pub host: String,
+    // the AASA is fetched from the server instead of apple's CDN, only on devices with
+    // Associated Domains Development enabled in the developer settings
+    pub developer: bool,
+    // only for apps installed by an MDM that allows managed domains
+    pub managed: bool,
+}
+
+impl AssociatedDomain {
+    pub fn parse(entry: &str) -> Option<AssociatedDomain> {
+        let colon = entry.find(':')?;
+        let (host, query) = match entry.find('?') {
+            Some(q) if q > colon => (&entry[colon + 1..q], Some(&entry[q + 1..])),
+            _ => (&entry[colon + 1..], None),
+        };
+        let mut ad = AssociatedDomain {
+            entry: entry.to_string(),
+            service: entry[..colon].to_string(),
+            host: host.to_lowercase(),
+            developer: false,
+            managed: false,
+        };
+        if let Some(query) = query {
+            let modes = query.split('&').find(|p| p.starts_with("mode=")).map(|p| &p[5..]).unwrap_or("");
+            for mode in modes.split('+') {
+                match mode {
+                    "developer" => ad.developer = true,
+                    "managed" => ad.managed = true,
+                    _ => {}
+                }
+            }
+        }
+        Some(ad)
+    }
+
+    pub fn is_wildcard(&self) -> bool {
+        self.host.starts_with("*.")
+    }
+
+    // exact matches before wildcards, the longest wildcard, then entries without a mode
+    fn precedence(&self) -> (bool, usize, bool) {
+        (!self.is_wildcard(), self.host.len(), !self.developer && !self.managed)
+    }
+
+    // a wildcard covers the subdomains but not the domain itself
+    pub fn matches(&self, domain: &str) -> bool {
+        let domain = domain.to_lowercase();
+        if self.is_wildcard() {
+            domain.ends_with(&self.host[1..]) && domain.len() > self.host.len() - 1
+        } else {
+            self.host == domain
+        }
+    }
+}
+
+#[test]
+fn test_associated_domain() {
+    let ad = AssociatedDomain::parse("applinks:*.Example.com?mode=developer+managed").unwrap();
+    assert_eq!("applinks", ad.service);
+    assert_eq!("*.example.com", ad.host);
+    assert!(ad.developer && ad.managed && ad.is_wildcard());
+    assert!(ad.matches("www.example.com"));
+    assert!(ad.matches("a.b.example.com"));
+    assert!(!ad.matches("example.com"));
+    assert!(!ad.matches("badexample.com"));
+
+    let ad = AssociatedDomain::parse("webcredentials:example.com?mode=managed").unwrap();
+    assert!(!ad.developer && ad.managed);
+    assert!(ad.matches("EXAMPLE.com"));
+    assert!(AssociatedDomain::parse("example.com").is_none());
+
+    let mut entitlements = Entitlements::new();
+    entitlements.associated_domains = vec![
+        "webcredentials:example.com".to_string(),
+        "applinks:*.example.com".to_string(),
+        "applinks:www.example.com?mode=developer".to_string(),
+    ];
+    assert!(entitlements.applink_domain("example.com").is_none());
+    assert!(entitlements.applink_domain("www.example.com").unwrap().developer);
+    assert!(!entitlements.applink_domain("shop.example.com").unwrap().developer);
+
+    entitlements.associated_domains.push("applinks:www.example.com".to_string());
+    entitlements.associated_domains.push("applinks:*.shop.example.com?mode=managed".to_string());
+    entitlements.associated_domains.push("applinks:*.shop.example.com".to_string());
+    assert_eq!("applinks:www.example.com", entitlements.applink_domain("www.example.com").unwrap().entry);
+    assert_eq!("applinks:*.shop.example.com", entitlements.applink_domain("a.shop.example.com").unwrap().entry);
+}
+
+#[test]
+fn test_from_dictionary() {
+    let mut dict = BTreeMap::new();
+    dict.insert("application-identifier".to_string(), Plist::String("TEAM.com.example.app".to_string()));
+    dict.insert(
+        "com.apple.developer.associated-domains".to_string(),
+        Plist::Array(vec![
+            Plist::String("applinks:example.com".to_string()),
+            Plist::Integer(1),
+            Plist::String("webcredentials:example.com".to_string()),
+        ]),
+    );
+    let entitlements = Entitlements::from_dictionary(&dict);
+    assert_eq!(Some("TEAM.com.example.app".to_string()), entitlements.application_identifier);
+    assert_eq!(None, entitlements.team_identifier);
+    assert_eq!(
+        vec!["applinks:example.com", "webcredentials:example.com"],
+        entitlements.associated_domains
+    );
+}
+
+#[derive(Debug)]
 pub enum Problem {
+    // no com.apple.developer.associated-domains entitlement or an empty one
+    MissingAssociatedDomains,
     DomainNotInApplinks,
+    // entries for the domain with another service, e.g. webcredentials:
+    DomainWithoutApplinks(Vec<String>),
     WrongBundleIdentifier,
+    // keys that differ, iOS 15 and later only read the DER entitlements
+    DerEntitlementsDiffer(Vec<String>),
+    // architecture the entitlements are reported for and the architectures with other entitlements
+    ArchitecturesDiffer(String, Vec<String>),
+    AdHocSigned,
+    // team of the code directory and of the entitlements
+    SigningTeamMismatch(String, String),
+    // the matching associated domains entry
+    DeveloperMode(String),
+    ManagedMode(String),
 }
 
 impl Problem {
     pub fn to_string_human(&self) -> String {
         match self {
+            Problem::MissingAssociatedDomains => {
+                "The entitlements have no associated domains, the Associated Domains capability is not enabled for the app.".to_string()
+            }
             Problem::DomainNotInApplinks => {
                 "The domain is not listed in the entitlements.".to_string()
             }
+            Problem::DomainWithoutApplinks(entries) => format!(
+                "The domain is only listed as {}, universal links need an applinks: entry.",
+                entries.join(", ")
+            ),
             Problem::WrongBundleIdentifier => {
                 "The bundle identifier in the entitlements does not match the one you supplied."
                     .to_string()
             }
+            Problem::DerEntitlementsDiffer(keys) => format!(
+                "The XML and DER entitlements of the code signature differ in {}. Newer iOS versions use the DER entitlements.",
+                keys.join(", ")
+            ),
+            Problem::ArchitecturesDiffer(architecture, others) => format!(
+                "The entitlements of {} differ from those of {}, the binaries were signed separately.",
+                others.join(", "),
+                architecture
+            ),
+            Problem::AdHocSigned => "The executable is ad hoc signed, it cannot be installed on a device and its entitlements are not checked against a provisioning profile.".to_string(),
+            Problem::SigningTeamMismatch(signing, entitlements) => format!(
+                "The executable is signed by team '{}' but the entitlements belong to team '{}'.",
+                signing, entitlements
+            ),
+            Problem::DeveloperMode(entry) => format!(
+                "'{}' is in developer mode: the association file is fetched directly from your server instead of apple's CDN, and only on devices with Associated Domains Development enabled in the developer settings.",
+                entry
+            ),
+            Problem::ManagedMode(entry) => format!(
+                "'{}' is in managed mode: it only applies when the app is installed by an MDM profile that allows the domain.",
+                entry
+            ),
         }
     }
 }
 
-pub fn extract_info_from_ipa(ipa: &str) -> Option<Entitlements> {
-    if let Ok(buf) = extract_executable_from_ipa(ipa) {
-        return extract_info_from_plist(&buf);
+#[derive(Debug)]
+pub enum Error {
+    InvalidMachO,
+    EmptyFatFile,
+    // archives and other files that are not executables
+    UnsupportedFileType,
+    // architecture whose slice lies outside of the fat file
+    SliceOutOfRange(String),
+    CodeSignature(codesign::Error),
+    InvalidXmlEntitlements,
+    InvalidDerEntitlements,
+    // no slice has a code signature
+    Unsigned,
+    // path of a file that could not be read
+    Unreadable(String),
+    InvalidEntitlementsFile,
+}
+
+impl Error {
+    pub fn to_string_human(&self) -> String {
+        match self {
+            Error::InvalidMachO => "The executable is not a valid Mach-O file.".to_string(),
+            Error::EmptyFatFile => "The executable is a fat file without architectures.".to_string(),
+            Error::UnsupportedFileType => "The executable is neither a Mach-O nor a fat file.".to_string(),
+            Error::SliceOutOfRange(architecture) => {
+                format!("The {} slice lies outside of the fat file.", architecture)
+            }
+            Error::CodeSignature(e) => e.to_string_human(),
+            Error::InvalidXmlEntitlements => "The XML entitlements of the code signature are not a valid plist.".to_string(),
+            Error::InvalidDerEntitlements => "The DER entitlements of the code signature could not be decoded.".to_string(),
+            Error::Unsigned => "The executable is not code signed, it has no entitlements.".to_string(),
+            Error::Unreadable(path) => format!("The file '{}' could not be read.", path),
+            Error::InvalidEntitlementsFile => "The entitlements file is not a valid plist dictionary.".to_string(),
+        }
     }
-    None
 }
 
-fn extract_executable_from_ipa(ipa: &str) -> zip::result::ZipResult<Vec<u8>> {
-    let file = File::open(ipa)?;
+impl From<codesign::Error> for Error {
+    fn from(e: codesign::Error) -> Self {
+        Error::CodeSignature(e)
+    }
+}
 
-    let mut zip = zip::ZipArchive::new(file)?;
+impl From<der::Error> for Error {
+    fn from(_: der::Error) -> Self {
+        Error::InvalidDerEntitlements
+    }
+}
 
-    let mut candidates: Vec<(usize, usize)> = Vec::new();
+// an executable, or an Xcode .entitlements plist for checks before the app is signed
+pub fn extract_info_from_file(file_name: &str) -> Result<Option<Entitlements>, Error> {
+    let buf = fs::read(file_name).map_err(|_| Error::Unreadable(file_name.to_string()))?;
+    if file_name.ends_with(".entitlements") {
+        return extract_info_from_entitlements_plist(&buf).map(Some);
+    }
+    extract_info_from_plist(&buf)
+}
 
-    for i in 0..zip.len() {
-        let file = zip.by_index(i).unwrap();
-        if file.name().ends_with("/Info.plist") {
-            candidates.push((i, file.name().len()));
+// the entitlements of an Xcode project, build settings like $(TeamIdentifierPrefix) are not expanded
+pub fn extract_info_from_entitlements_plist(buf: &[u8]) -> Result<Entitlements, Error> {
+    match Plist::read(Cursor::new(buf)) {
+        Ok(Plist::Dictionary(dict)) => {
+            let mut entitlements = Entitlements::from_dictionary(&dict);
+            // usually $(AppIdentifierPrefix)$(CFBundleIdentifier), only known after the build
+            if entitlements.application_identifier.as_ref().map_or(false, |a| a.contains("$(")) {
+                entitlements.application_identifier = None;
+            }
+            Ok(entitlements)
         }
+        _ => Err(Error::InvalidEntitlementsFile),
     }
+}
 
-    candidates.sort_by(|c1, c2| c1.1.cmp(&c2.1));
-
-    // TODO: allocate with capacity from previous loops file size
-    let mut buf: Vec<u8> = Vec::new();
-    {
-        let mut file = zip.by_index(candidates[0].0)?;
-        file.read_to_end(&mut buf)?;
+pub fn extract_info_from_plist(buf: &[u8]) -> Result<Option<Entitlements>, Error> {
+    let signatures = extract_code_signatures(&buf)?;
+    // simulator builds and binaries that were never run through codesign
+    if signatures.iter().all(|s| s.1.is_none()) {
+        return Err(Error::Unsigned);
+    }
+    // the first signed slice stands for all, xcode signs every slice with the same entitlements
+    let reference = signatures.iter().position(|s| s.1.is_some()).unwrap_or(0);
+    let (code_directory, ad_hoc) = match signatures[reference].1 {
+        Some(ref signature) => (signature.code_directory()?, signature.is_ad_hoc()?),
+        None => (None, false),
+    };
+    let mut slices = Vec::new();
+    for (architecture, signature) in signatures {
+        let plist = match signature {
+            Some(signature) => Some(entitlements_plist(&signature)?),
+            None => None,
+        };
+        slices.push((architecture, plist));
     }
 
-    let cur = Cursor::new(&buf[..]);
+    let (architecture, first) = slices.remove(reference);
+    let first = first.unwrap_or(None);
+    // unsigned slices always differ
+    let architecture_mismatches: Vec<String> = slices
+        .iter()
+        .filter(|s| match s.1 {
+            Some(ref plist) => plist.as_ref().map(|p| &p.0) != first.as_ref().map(|p| &p.0),
+            None => true,
+        })
+        .map(|s| s.0.clone())
+        .collect();
+    let (parsed, der_mismatches) = match first {
+        Some(first) => first,
+        None => return Ok(None),
+    };
 
-    if let Ok(Plist::Dictionary(parsed)) = Plist::read(cur) {
-        if let Some(Plist::String(executable)) = parsed.get("CFBundleExecutable") {
-            if let Some(Plist::String(bundle_name)) = parsed.get("CFBundleName") {
-                let mut fname = vec![];
-                write!(&mut fname, "Payload/{}.app/{}", bundle_name, executable)?;
-                let fname = String::from_utf8(fname).unwrap();
-                if let Ok(mut file) = zip.by_name(&fname) {
-                    let mut buf = Vec::with_capacity(file.size() as usize);
-                    if file.read_to_end(&mut buf).is_ok() {
-                        return Ok(buf);
-                    }
-                }
+    match parsed {
+        Plist::Dictionary(parsed) => {
+            let mut entitlements = Entitlements::from_dictionary(&parsed);
+            entitlements.der_mismatches = der_mismatches;
+            entitlements.architecture = architecture;
+            entitlements.architecture_mismatches = architecture_mismatches;
+            if let Some(code_directory) = code_directory {
+                entitlements.signing_identifier = Some(code_directory.identifier);
+                entitlements.signing_team_id = code_directory.team_id;
             }
+            entitlements.ad_hoc = ad_hoc;
+            Ok(Some(entitlements))
         }
+        _ => Err(Error::InvalidXmlEntitlements),
     }
-
-    Err(ZipError::FileNotFound)
 }
 
-pub fn extract_info_from_file(file_name: &str) -> Option<Entitlements> {
-    let mut fp = File::open(file_name).unwrap();
-    let mut buf = Vec::with_capacity(100);
-    fp.read_to_end(&mut buf).expect("error reading file");
-    extract_info_from_plist(&buf)
+// the entitlements of a signature, the XML form if present, and the keys where XML and DER disagree
+fn entitlements_plist(signature: &CodeSignature) -> Result<Option<(Plist, Vec<String>)>, Error> {
+    let xml = match signature.entitlements() {
+        Some(xml) => Some(Plist::read(Cursor::new(xml)).map_err(|_| Error::InvalidXmlEntitlements)?),
+        None => None,
+    };
+    let der = match signature.der_entitlements() {
+        Some(der) => Some(plist_from_der(der)?),
+        None => None,
+    };
+
+    Ok(match (xml, der) {
+        (Some(xml), Some(der)) => {
+            let der_mismatches = differing_keys(&xml, &der);
+            Some((xml, der_mismatches))
+        }
+        (Some(xml), None) => Some((xml, Vec::new())),
+        (None, Some(der)) => Some((der, Vec::new())),
+        (None, None) => None,
+    })
 }
 
-pub fn extract_info_from_plist(buf: &[u8]) -> Option<Entitlements> {
-    if let Some(plist) = extract_entitlements_plist(&buf) {
-        let cursor = Cursor::new(plist);
-        if let Ok(Plist::Dictionary(parsed)) = Plist::read(cursor) {
-            let mut entitlements = Entitlements::new();
+const DER_TAG_BOOLEAN: u8 = 0x01;
+const DER_TAG_INTEGER: u8 = 0x02;
+const DER_TAG_UTF8_STRING: u8 = 0x0c;
+// [APPLICATION 16] around the version and the entitlements dictionary
+const DER_TAG_ENTITLEMENTS: u8 = 0x70;
+// [CONTEXT 16], a dictionary of (key, value) sequences
+const DER_TAG_DICTIONARY: u8 = 0xb0;
 
-            if let Some(Plist::String(app_id)) = parsed.get("application-identifier") {
-                entitlements.application_identifier = Some(app_id.clone());
-            }
+// decodes the DER entitlements blob into the plist it was generated from
+fn plist_from_der(buf: &[u8]) -> Result<Plist, Error> {
+    let (outer, _) = der::parse(buf)?;
+    if outer.tag != DER_TAG_ENTITLEMENTS {
+        return Err(Error::InvalidDerEntitlements);
+    }
+    match &outer.children()?[..] {
+        [version, dict] if version.tag == DER_TAG_INTEGER && dict.tag == DER_TAG_DICTIONARY => {
+            plist_from_der_value(dict)
+        }
+        _ => Err(Error::InvalidDerEntitlements),
+    }
+}
 
-            if let Some(Plist::Array(assoc_doms)) =
-                parsed.get("com.apple.developer.associated-domains")
-            {
-                for dom in assoc_doms {
-                    if let Plist::String(dom) = dom {
-                        entitlements.associated_domains.push(dom.clone());
+fn plist_from_der_value(value: &der::Tlv) -> Result<Plist, Error> {
+    match value.tag {
+        DER_TAG_BOOLEAN => match value.content {
+            [b] => Ok(Plist::Boolean(*b != 0)),
+            _ => Err(Error::InvalidDerEntitlements),
+        },
+        DER_TAG_INTEGER => {
+            if value.content.is_empty() || value.content.len() > 8 {
+                return Err(Error::InvalidDerEntitlements);
+            }
+            // two's complement, the first byte carries the sign
+            let first = i64::from(value.content[0] as i8);
+            let int = value.content[1..].iter().fold(first, |acc, &b| acc << 8 | i64::from(b));
+            Ok(Plist::Integer(int))
+        }
+        DER_TAG_UTF8_STRING => String::from_utf8(value.content.to_vec())
+            .map(Plist::String)
+            .map_err(|_| Error::InvalidDerEntitlements),
+        der::TAG_SEQUENCE => {
+            let items: Result<Vec<Plist>, Error> = value.children()?.iter().map(plist_from_der_value).collect();
+            Ok(Plist::Array(items?))
+        }
+        DER_TAG_DICTIONARY => {
+            let mut dict = BTreeMap::new();
+            for entry in value.children()? {
+                match &entry.children()?[..] {
+                    [key, value] if entry.tag == der::TAG_SEQUENCE && key.tag == DER_TAG_UTF8_STRING => {
+                        let key = String::from_utf8(key.content.to_vec()).map_err(|_| Error::InvalidDerEntitlements)?;
+                        dict.insert(key, plist_from_der_value(value)?);
                     }
+                    _ => return Err(Error::InvalidDerEntitlements),
                 }
             }
-
-            return Some(entitlements);
+            Ok(Plist::Dictionary(dict))
         }
+        _ => Err(Error::InvalidDerEntitlements),
     }
-    None
 }
 
-fn extract_entitlements_plist(buf: &[u8]) -> Option<&[u8]> {
-    let mut cur = Cursor::new(&buf[..]);
+// the top level keys that are missing in one of the entitlements or have different values
+fn differing_keys(xml: &Plist, der: &Plist) -> Vec<String> {
+    let empty = BTreeMap::new();
+    let xml = xml.as_dictionary().unwrap_or(&empty);
+    let der = der.as_dictionary().unwrap_or(&empty);
+    let mut keys: Vec<String> = xml
+        .keys()
+        .chain(der.keys())
+        .filter(|k| xml.get(*k) != der.get(*k))
+        .cloned()
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
 
-    let mach_file = match OFile::parse(&mut cur).unwrap() {
-        OFile::FatFile { files, .. } => {
-            if files.is_empty() {
-                panic!("FatFile with 0 architectures");
-            }
-            // return first MachFile from FatFile
-            files[0].1.clone()
-        }
-        f @ OFile::MachFile { .. } => f,
-        t => panic!("unknown Mach-O filetype {:?}", t),
-    };
+#[test]
+fn test_plist_from_der() {
+    use crate::der::build_tlv as tlv;
+    fn entry(key: &str, value: Vec<u8>) -> Vec<u8> {
+        let mut content = tlv(DER_TAG_UTF8_STRING, key.as_bytes());
+        content.extend(value);
+        tlv(der::TAG_SEQUENCE, &content)
+    }
 
-    if let OFile::MachFile { ref commands, .. } = mach_file {
-        for cmd in commands {
-            if let LoadCommand::CodeSignature(ldcmd) = cmd.command() {
-                let start = ldcmd.off as usize;
-                let end = ldcmd.off as usize + ldcmd.size as usize;
-                let data = &buf[start..end];
-                if let Some((offset, length)) = find_codesign_plist(data) {
-                    let start = offset;
-                    // the length contains the 4 magic bytes and the 4 length bytes
-                    let end = offset + length - 8;
-                    return Some(&data[start..end]);
-                }
-            }
-        }
+    let mut domains = tlv(DER_TAG_UTF8_STRING, b"applinks:example.com");
+    domains.extend(tlv(DER_TAG_UTF8_STRING, b"webcredentials:example.com"));
+    let mut dict = entry("application-identifier", tlv(DER_TAG_UTF8_STRING, b"TEAM.com.example"));
+    dict.extend(entry("com.apple.developer.associated-domains", tlv(der::TAG_SEQUENCE, &domains)));
+    dict.extend(entry("get-task-allow", tlv(DER_TAG_BOOLEAN, &[0xff])));
+    dict.extend(entry("version", tlv(DER_TAG_INTEGER, &[0xff, 0x00])));
+    let mut content = tlv(DER_TAG_INTEGER, &[1]);
+    content.extend(tlv(DER_TAG_DICTIONARY, &dict));
+    let buf = tlv(DER_TAG_ENTITLEMENTS, &content);
+
+    let der = plist_from_der(&buf).unwrap();
+    let dict = der.as_dictionary().unwrap();
+    assert_eq!(Some(&Plist::String("TEAM.com.example".to_string())), dict.get("application-identifier"));
+    assert_eq!(Some(&Plist::Boolean(true)), dict.get("get-task-allow"));
+    assert_eq!(Some(&Plist::Integer(-256)), dict.get("version"));
+    match dict.get("com.apple.developer.associated-domains") {
+        Some(Plist::Array(domains)) => assert_eq!(2, domains.len()),
+        d => panic!("unexpected domains {:?}", d),
+    }
+
+    let mut xml = dict.clone();
+    xml.insert("get-task-allow".to_string(), Plist::Boolean(false));
+    xml.remove("version");
+    assert_eq!(vec!["get-task-allow", "version"], differing_keys(&Plist::Dictionary(xml), &der));
+    assert!(differing_keys(&der, &der).is_empty());
+
+    assert!(plist_from_der(&tlv(DER_TAG_ENTITLEMENTS, &tlv(DER_TAG_INTEGER, &[1]))).is_err());
+}
+
+const CPU_TYPE_X86: cpu_type_t = 7;
+const CPU_TYPE_ARM: cpu_type_t = 12;
+const CPU_ARCH_ABI64: cpu_type_t = 0x0100_0000;
+// the upper bits of the subtype are capability flags
+const CPU_SUBTYPE_MASK: cpu_subtype_t = 0x00ff_ffff;
+
+fn architecture_name(cputype: cpu_type_t, cpusubtype: cpu_subtype_t) -> String {
+    match (cputype, cpusubtype & CPU_SUBTYPE_MASK) {
+        (CPU_TYPE_X86, _) => "i386".to_string(),
+        (t, _) if t == CPU_TYPE_X86 | CPU_ARCH_ABI64 => "x86_64".to_string(),
+        (CPU_TYPE_ARM, 9) => "armv7".to_string(),
+        (CPU_TYPE_ARM, 11) => "armv7s".to_string(),
+        (CPU_TYPE_ARM, _) => "arm".to_string(),
+        (t, 2) if t == CPU_TYPE_ARM | CPU_ARCH_ABI64 => "arm64e".to_string(),
+        (t, _) if t == CPU_TYPE_ARM | CPU_ARCH_ABI64 => "arm64".to_string(),
+        (t, s) => format!("cputype {} subtype {}", t, s),
     }
+}
 
-    None
+#[test]
+fn test_architecture_name() {
+    assert_eq!("arm64", architecture_name(0x0100_000c, 0));
+    assert_eq!("arm64e", architecture_name(0x0100_000c, 0x8000_0002u32 as i32));
+    assert_eq!("armv7s", architecture_name(12, 11));
+    assert_eq!("x86_64", architecture_name(0x0100_0007, 3));
 }
 
-fn find_codesign_plist(buf: &[u8]) -> Option<(usize, usize)> {
-    if let Some(offset) = find_codesign_magic_offset(buf) {
-        let mut idx = offset + 4;
-        let mut pl_len = (buf[idx] as usize) << 24;
-        idx += 1;
-        pl_len += (buf[idx] as usize) << 16;
-        idx += 1;
-        pl_len += (buf[idx] as usize) << 8;
-        idx += 1;
-        pl_len += buf[idx] as usize;
-        idx += 1;
-        return Some((idx, pl_len));
+// f applied to every slice of a fat file, or to the file itself, with the architecture name
+pub fn map_slices<'a, T, F>(buf: &'a [u8], mut f: F) -> Result<Vec<(String, T)>, Error>
+where
+    F: FnMut(&OFile, &'a [u8]) -> Result<T, Error>,
+{
+    let mut cur = Cursor::new(&buf[..]);
+    let file = OFile::parse(&mut cur).map_err(|_| Error::InvalidMachO)?;
+    match file {
+        OFile::FatFile { ref files, .. } => {
+            if files.is_empty() {
+                return Err(Error::EmptyFatFile);
+            }
+            let mut res = Vec::with_capacity(files.len());
+            for (arch, file) in files {
+                let name = architecture_name(arch.cputype, arch.cpusubtype);
+                // load command offsets are relative to the start of the slice
+                let start = arch.offset as usize;
+                let slice = match start.checked_add(arch.size as usize).and_then(|end| buf.get(start..end)) {
+                    Some(slice) => slice,
+                    None => return Err(Error::SliceOutOfRange(name)),
+                };
+                let value = f(file, slice)?;
+                res.push((name, value));
+            }
+            Ok(res)
+        }
+        OFile::MachFile { ref header, .. } => {
+            let name = architecture_name(header.cputype, header.cpusubtype);
+            let value = f(&file, buf)?;
+            Ok(vec![(name, value)])
+        }
+        _ => Err(Error::UnsupportedFileType),
     }
-    None
 }
 
-const MAGIC_BYTES: [u8; 4] = [0xfa, 0xde, 0x71, 0x71];
+// the code signatures of all architectures in a thin or fat binary
+fn extract_code_signatures(buf: &[u8]) -> Result<Vec<(String, Option<CodeSignature<'_>>)>, Error> {
+    map_slices(buf, extract_code_signature)
+}
 
-fn find_codesign_magic_offset(buf: &[u8]) -> Option<usize> {
-    for (offset, window) in buf.windows(4).enumerate() {
-        if window == MAGIC_BYTES {
-            return Some(offset);
+fn extract_code_signature<'a>(file: &OFile, buf: &'a [u8]) -> Result<Option<CodeSignature<'a>>, Error> {
+    if let OFile::MachFile { ref commands, .. } = file {
+        for cmd in commands {
+            if let LoadCommand::CodeSignature(ldcmd) = cmd.command() {
+                let start = ldcmd.off as usize;
+                let end = start + ldcmd.size as usize;
+                let data = buf.get(start..end).ok_or(codesign::Error::Truncated)?;
+                return Ok(Some(codesign::parse(data)?));
+            }
         }
     }
-    None
+
+    Ok(None)
 }
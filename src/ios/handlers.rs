@@ -0,0 +1,113 @@
+use crate::ios::entitlements::{self, Error};
+use mach_object::{LoadCommand, OFile};
+
+// an app receives universal links through an NSUserActivity passed to the app or scene delegate, or
+// through the SwiftUI modifiers. the selectors are in __objc_methname, swift @objc methods included.
+// the SwiftUI modifiers are imported symbols, matched by the mangled name of the View extension.
+// __objc_methname holds every selector the binary references, not only the implemented ones. an SDK
+// that merely forwards application:continueUserActivity:restorationHandler: hides a missing handler.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Handler {
+    AppDelegate,
+    SceneDelegate,
+    ContinueUserActivity,
+    OpenUrl,
+}
+
+impl Handler {
+    pub fn to_string_human(self) -> &'static str {
+        match self {
+            Handler::AppDelegate => "application(_:continue:restorationHandler:)",
+            Handler::SceneDelegate => "scene(_:continue:)",
+            Handler::ContinueUserActivity => "onContinueUserActivity",
+            Handler::OpenUrl => "onOpenURL",
+        }
+    }
+}
+
+const SELECTORS: &[(&str, Handler)] = &[
+    ("application:continueUserActivity:restorationHandler:", Handler::AppDelegate),
+    ("scene:continueUserActivity:", Handler::SceneDelegate),
+];
+
+const SWIFTUI_SYMBOLS: &[(&str, Handler)] = &[
+    ("7SwiftUI4ViewPAAE22onContinueUserActivity", Handler::ContinueUserActivity),
+    ("7SwiftUI4ViewPAAE9onOpenURL", Handler::OpenUrl),
+];
+
+#[derive(Debug)]
+pub enum Problem {
+    NoHandler,
+}
+
+impl Problem {
+    pub fn to_string_human(&self) -> String {
+        match self {
+            Problem::NoHandler => "The app does not seem to handle universal links, neither application(_:continue:restorationHandler:), scene(_:continue:) nor onContinueUserActivity or onOpenURL were found in the executable.".to_string(),
+        }
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+// methnames is the __objc_methname section, symbols the string table of the symbol table
+fn handlers_in(methnames: &[u8], symbols: &[u8]) -> Vec<Handler> {
+    let mut res = Vec::new();
+    for (selector, handler) in SELECTORS {
+        if methnames.split(|&b| b == 0).any(|m| m == selector.as_bytes()) {
+            res.push(*handler);
+        }
+    }
+    for (symbol, handler) in SWIFTUI_SYMBOLS {
+        if contains(symbols, symbol.as_bytes()) {
+            res.push(*handler);
+        }
+    }
+    res
+}
+
+#[test]
+fn test_handlers_in() {
+    let methnames = b"init\0application:didFinishLaunchingWithOptions:\0scene:continueUserActivity:\0";
+    let symbols = b"\0_OBJC_CLASS_$_NSUserActivity\0_$s7SwiftUI4ViewPAAE9onOpenURL7performQrySy10Foundation0F0Vc_tF\0";
+    assert_eq!(
+        vec![Handler::SceneDelegate, Handler::OpenUrl],
+        handlers_in(methnames, symbols)
+    );
+    // only whole selectors count
+    assert!(handlers_in(b"xscene:continueUserActivity:\0", b"").is_empty());
+    // an app function with the same name is not the SwiftUI modifier
+    assert!(handlers_in(b"", b"\0_$s5MyApp9onOpenURLyyF\0").is_empty());
+}
+
+fn find_handlers_in_file(file: &OFile, buf: &[u8]) -> Result<Vec<Handler>, Error> {
+    let mut methnames: &[u8] = &[];
+    let mut symbols: &[u8] = &[];
+    if let OFile::MachFile { ref commands, .. } = file {
+        for cmd in commands {
+            match cmd.command() {
+                LoadCommand::Segment { ref sections, .. } | LoadCommand::Segment64 { ref sections, .. } => {
+                    for section in sections.iter().filter(|s| s.sectname == "__objc_methname") {
+                        let start = section.offset as usize;
+                        methnames = start.checked_add(section.size).and_then(|end| buf.get(start..end)).unwrap_or(&[]);
+                    }
+                }
+                LoadCommand::SymTab { stroff, strsize, .. } => {
+                    let start = *stroff as usize;
+                    symbols = start.checked_add(*strsize as usize).and_then(|end| buf.get(start..end)).unwrap_or(&[]);
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(handlers_in(methnames, symbols))
+}
+
+// the handlers of the first slice, all slices are built from the same sources
+pub fn find_handlers(buf: &[u8]) -> Result<Vec<Handler>, Error> {
+    let mut slices = entitlements::map_slices(buf, find_handlers_in_file)?;
+    Ok(slices.remove(0).1)
+}
@@ -0,0 +1,388 @@
+use crate::ios::entitlements::{self, Entitlements};
+use crate::ios::handlers::{self, Handler};
+use crate::ios::provisioning::{self, ProvisioningProfile};
+use plist::Plist;
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+// an IPA contains the app and the bundles embedded in it: app extensions (PlugIns/*.appex),
+// App Clips (AppClips/*.app) and watch apps (Watch/*.app), each signed with its own entitlements.
+// the same layout is read from an unzipped .app and from the Products/Applications of an .xcarchive.
+
+#[derive(Debug)]
+pub struct Bundle {
+    // the bundle directory inside the IPA, e.g. Payload/App.app/PlugIns/Share.appex, or the
+    // checked file for a bare executable or .entitlements file
+    pub path: String,
+    main: bool,
+    // false when the Info.plist could not be read or the input has none
+    info_plist: bool,
+    // CFBundleIdentifier of the Info.plist
+    pub identifier: Option<String>,
+    pub entitlements: Option<Entitlements>,
+    // the link handlers found in the executable, none for .entitlements files
+    pub handlers: Option<Vec<Handler>>,
+    // embedded.mobileprovision, missing for builds that were not exported for a device
+    pub profile: Option<ProvisioningProfile>,
+    // the reasons why parts of the bundle could not be read
+    pub problems: Vec<Problem>,
+}
+
+impl Bundle {
+    fn new(path: String, main: bool) -> Bundle {
+        Bundle {
+            path,
+            main,
+            info_plist: false,
+            identifier: None,
+            entitlements: None,
+            handlers: None,
+            profile: None,
+            problems: Vec::new(),
+        }
+    }
+
+    pub fn is_main(&self) -> bool {
+        self.main
+    }
+
+    // codesign uses the bundle identifier unless told otherwise, a mismatch means the executable was re-signed
+    fn check_signing_identifier(&self) -> Option<Problem> {
+        let signing = self.entitlements.as_ref()?.signing_identifier.as_ref()?;
+        match self.identifier {
+            Some(ref identifier) if identifier != signing => {
+                Some(Problem::SigningIdentifierMismatch(signing.clone(), identifier.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn check_identifier(&self, bundle_identifier: &str) -> Option<Problem> {
+        if !self.info_plist {
+            return None;
+        }
+        match self.identifier {
+            Some(ref identifier) if identifier != bundle_identifier => Some(Problem::BundleIdentifierMismatch(
+                identifier.clone(),
+                bundle_identifier.to_string(),
+            )),
+            Some(_) => None,
+            None => Some(Problem::MissingBundleIdentifier),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    // path that does not exist
+    NotFound(String),
+    InvalidIpa,
+    UnreadableDirectory(String),
+    // a directory that is neither an .app nor an .xcarchive
+    UnsupportedDirectory(String),
+    // no .app directory in the parent that was searched, Payload/ or Products/Applications/
+    NoMainBundle(String),
+    Io(String),
+}
+
+impl Error {
+    pub fn to_string_human(&self) -> String {
+        match self {
+            Error::NotFound(path) => format!("'{}' does not exist.", path),
+            Error::InvalidIpa => "The IPA is not a valid zip file.".to_string(),
+            Error::UnreadableDirectory(path) => format!("The directory '{}' could not be read.", path),
+            Error::UnsupportedDirectory(path) => {
+                format!("'{}' is a directory but neither an .app nor an .xcarchive.", path)
+            }
+            Error::NoMainBundle(parent) => format!("The input contains no app, {}*.app is missing.", parent),
+            Error::Io(e) => format!("The input could not be read: {}", e),
+        }
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(e: zip::result::ZipError) -> Self {
+        match e {
+            zip::result::ZipError::Io(e) => e.into(),
+            _ => Error::InvalidIpa,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e.to_string())
+    }
+}
+
+#[derive(Debug)]
+pub enum Problem {
+    // app directories besides the one that was checked
+    MultipleApps(Vec<String>),
+    InfoPlistNotFound,
+    InvalidInfoPlist,
+    MissingBundleExecutable,
+    // path of the executable named in the Info.plist
+    ExecutableNotFound(String),
+    // identifier of the code signature and CFBundleIdentifier
+    SigningIdentifierMismatch(String, String),
+    MissingBundleIdentifier,
+    // CFBundleIdentifier and the bundle identifier that was asked for
+    BundleIdentifierMismatch(String, String),
+    Entitlements(entitlements::Error),
+    Profile(provisioning::Error),
+}
+
+impl Problem {
+    pub fn to_string_human(&self) -> String {
+        match self {
+            Problem::MultipleApps(others) => format!(
+                "The input contains more than one app, {} were not checked.",
+                others.join(", ")
+            ),
+            Problem::InfoPlistNotFound => "The bundle has no Info.plist.".to_string(),
+            Problem::InvalidInfoPlist => "The Info.plist of the bundle is not a valid plist.".to_string(),
+            Problem::MissingBundleExecutable => "The Info.plist of the bundle has no CFBundleExecutable.".to_string(),
+            Problem::ExecutableNotFound(path) => format!("The executable '{}' is missing.", path),
+            Problem::SigningIdentifierMismatch(signing, identifier) => format!(
+                "The executable is signed with identifier '{}' but the bundle identifier is '{}'.",
+                signing, identifier
+            ),
+            Problem::MissingBundleIdentifier => "The Info.plist of the bundle has no CFBundleIdentifier.".to_string(),
+            Problem::BundleIdentifierMismatch(identifier, requested) => format!(
+                "The bundle identifier of the app is '{}', not '{}'.",
+                identifier, requested
+            ),
+            Problem::Entitlements(e) => e.to_string_human(),
+            Problem::Profile(e) => e.to_string_human(),
+        }
+    }
+}
+
+fn is_bundle_dir(dir: &str) -> bool {
+    dir.ends_with(".app") || dir.ends_with(".appex")
+}
+
+// the app directories directly in Payload/ of an IPA or Products/Applications/ of an archive, found by
+// the paths of their entries because directories don't always have their own zip entry
+fn app_dirs(names: &[String], parent: &str) -> Vec<String> {
+    let mut dirs: Vec<String> = names
+        .iter()
+        .filter(|name| name.starts_with(parent))
+        .filter_map(|name| name[parent.len()..].find('/').map(|end| &name[..parent.len() + end]))
+        .filter(|dir| dir.ends_with(".app"))
+        .map(|dir| dir.to_string())
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    dirs
+}
+
+// the bundles nested in an app that have an Info.plist: extensions, App Clips and watch apps.
+// frameworks and resource bundles have an Info.plist as well. sorted by depth.
+fn nested_bundle_dirs(names: &[String], app_dir: &str) -> Vec<String> {
+    let prefix = format!("{}/", app_dir);
+    let mut dirs: Vec<String> = names
+        .iter()
+        .filter(|name| name.starts_with(&prefix) && name.ends_with("/Info.plist"))
+        .map(|name| name[..name.len() - "/Info.plist".len()].to_string())
+        .filter(|dir| dir != app_dir && is_bundle_dir(dir))
+        .collect();
+    dirs.sort_by_key(|dir| (dir.matches('/').count(), dir.clone()));
+    dirs
+}
+
+#[test]
+fn test_bundle_dirs() {
+    let names: Vec<String> = [
+        "Payload/App.app/Frameworks/Lib.framework/Info.plist",
+        "Payload/App.app/Watch/Watch.app/PlugIns/WatchExtension.appex/Info.plist",
+        "Payload/App.app/PlugIns/Share.appex/Info.plist",
+        "Payload/App.app/App",
+        "Payload/App.app/Info.plist",
+        "Payload/App.app/AppClips/Clip.app/Info.plist",
+        "Payload/App.app/Settings.bundle/Info.plist",
+        "Payload/App.app/Watch/Watch.app/Info.plist",
+        "Payload/Other.app/Other",
+        "Payload/README",
+    ]
+    .iter()
+    .map(|n| n.to_string())
+    .collect();
+    assert_eq!(vec!["Payload/App.app", "Payload/Other.app"], app_dirs(&names, "Payload/"));
+    assert!(app_dirs(&names, "Products/Applications/").is_empty());
+    assert_eq!(
+        vec![
+            "Payload/App.app/AppClips/Clip.app",
+            "Payload/App.app/PlugIns/Share.appex",
+            "Payload/App.app/Watch/Watch.app",
+            "Payload/App.app/Watch/Watch.app/PlugIns/WatchExtension.appex",
+        ],
+        nested_bundle_dirs(&names, "Payload/App.app")
+    );
+}
+
+fn read_zip_entry<R: Read + Seek>(zip: &mut ZipArchive<R>, name: &str) -> Option<Vec<u8>> {
+    let mut file = zip.by_name(name).ok()?;
+    let mut buf = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut buf).ok()?;
+    Some(buf)
+}
+
+// the files of an IPA or of a directory, by their path relative to the root
+enum Files {
+    Zip(ZipArchive<File>),
+    Dir(PathBuf),
+}
+
+impl Files {
+    fn read(&mut self, name: &str) -> Option<Vec<u8>> {
+        match self {
+            Files::Zip(zip) => read_zip_entry(zip, name),
+            Files::Dir(root) => fs::read(root.join(name)).ok(),
+        }
+    }
+}
+
+// the paths of all files below dir, prefixed with prefix. symlinks are not followed.
+fn dir_file_names(dir: &Path, prefix: &str, names: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            dir_file_names(&entry.path(), &format!("{}/", name), names)?;
+        } else {
+            names.push(name);
+        }
+    }
+    Ok(())
+}
+
+fn read_bundle(files: &mut Files, dir: String, main: bool) -> Bundle {
+    let mut bundle = Bundle::new(dir, main);
+    let info = match files.read(&format!("{}/Info.plist", bundle.path)) {
+        Some(info) => info,
+        None => {
+            bundle.problems.push(Problem::InfoPlistNotFound);
+            return bundle;
+        }
+    };
+    let info = match Plist::read(Cursor::new(info)) {
+        Ok(Plist::Dictionary(info)) => info,
+        _ => {
+            bundle.problems.push(Problem::InvalidInfoPlist);
+            return bundle;
+        }
+    };
+    bundle.info_plist = true;
+    bundle.identifier = info.get("CFBundleIdentifier").and_then(|i| i.as_string()).map(|i| i.to_string());
+
+    // CFBundleExecutable usually but not always matches the directory name
+    let executable = match info.get("CFBundleExecutable").and_then(|e| e.as_string()) {
+        Some(executable) => format!("{}/{}", bundle.path, executable),
+        None => {
+            bundle.problems.push(Problem::MissingBundleExecutable);
+            return bundle;
+        }
+    };
+    match files.read(&executable) {
+        Some(buf) => {
+            match entitlements::extract_info_from_plist(&buf) {
+                Ok(entitlements) => bundle.entitlements = entitlements,
+                Err(e) => bundle.problems.push(Problem::Entitlements(e)),
+            }
+            if let Some(problem) = bundle.check_signing_identifier() {
+                bundle.problems.push(problem);
+            }
+            // an invalid executable is already reported for the entitlements
+            bundle.handlers = handlers::find_handlers(&buf).ok();
+        }
+        None => bundle.problems.push(Problem::ExecutableNotFound(executable)),
+    }
+
+    if let Some(buf) = files.read(&format!("{}/embedded.mobileprovision", bundle.path)) {
+        match provisioning::parse(&buf) {
+            Ok(profile) => bundle.profile = Some(profile),
+            Err(e) => bundle.problems.push(Problem::Profile(e)),
+        }
+    }
+    bundle
+}
+
+// the main app first, followed by the bundles nested in it
+fn read_bundles(mut files: Files, names: &[String], parent: &str, mut app_dirs: Vec<String>) -> Result<Vec<Bundle>, Error> {
+    if app_dirs.is_empty() {
+        return Err(Error::NoMainBundle(parent.to_string()));
+    }
+    let app_dir = app_dirs.remove(0);
+
+    let mut main = read_bundle(&mut files, app_dir.clone(), true);
+    if !app_dirs.is_empty() {
+        main.problems.push(Problem::MultipleApps(app_dirs));
+    }
+    let mut bundles = vec![main];
+    for dir in nested_bundle_dirs(names, &app_dir) {
+        bundles.push(read_bundle(&mut files, dir, false));
+    }
+    Ok(bundles)
+}
+
+fn extract_bundles_from_ipa(ipa: &str) -> Result<Vec<Bundle>, Error> {
+    let mut zip = ZipArchive::new(File::open(ipa)?)?;
+    let mut names = Vec::with_capacity(zip.len());
+    for i in 0..zip.len() {
+        names.push(zip.by_index(i)?.name().to_string());
+    }
+    let app_dirs = app_dirs(&names, "Payload/");
+    read_bundles(Files::Zip(zip), &names, "Payload/", app_dirs)
+}
+
+fn extract_bundles_from_dir(dir: &str) -> Result<Vec<Bundle>, Error> {
+    let dir = dir.trim_end_matches('/');
+    let path = Path::new(dir);
+    let unreadable = |_| Error::UnreadableDirectory(dir.to_string());
+    let mut names = Vec::new();
+    if dir.ends_with(".xcarchive") {
+        dir_file_names(path, "", &mut names).map_err(unreadable)?;
+        let app_dirs = app_dirs(&names, "Products/Applications/");
+        read_bundles(Files::Dir(path.to_path_buf()), &names, "Products/Applications/", app_dirs)
+    } else if dir.ends_with(".app") {
+        // the paths are relative to the parent so that the app is a directory like in an IPA
+        let app_dir = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let root = match path.parent() {
+            Some(parent) if parent != Path::new("") => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        dir_file_names(path, &format!("{}/", app_dir), &mut names).map_err(unreadable)?;
+        read_bundles(Files::Dir(root), &names, "", vec![app_dir])
+    } else {
+        Err(Error::UnsupportedDirectory(dir.to_string()))
+    }
+}
+
+// a bare executable or an Xcode .entitlements file, checked like the main app without an Info.plist
+fn extract_bundle_from_file(file_name: &str) -> Bundle {
+    let mut bundle = Bundle::new(file_name.to_string(), true);
+    match entitlements::extract_info_from_file(file_name) {
+        Ok(entitlements) => bundle.entitlements = entitlements,
+        Err(e) => bundle.problems.push(Problem::Entitlements(e)),
+    }
+    if !file_name.ends_with(".entitlements") {
+        bundle.handlers = fs::read(file_name).ok().and_then(|buf| handlers::find_handlers(&buf).ok());
+    }
+    bundle
+}
+
+// reads an IPA, an unzipped .app, an .xcarchive, a bare Mach-O executable or an .entitlements file
+pub fn extract_bundles(path: &str) -> Result<Vec<Bundle>, Error> {
+    let metadata = fs::metadata(path).map_err(|_| Error::NotFound(path.to_string()))?;
+    if metadata.is_dir() {
+        extract_bundles_from_dir(path)
+    } else if path.ends_with(".ipa") {
+        extract_bundles_from_ipa(path)
+    } else {
+        Ok(vec![extract_bundle_from_file(path)])
+    }
+}
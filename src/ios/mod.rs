@@ -1,12 +1,15 @@
 use clap::ArgMatches;
 use http::Uri;
-use std::fs;
 use crate::ios::aasa::fetch_and_check_sync;
 use std::process;
-use crate::ios::entitlements::extract_info_from_ipa;
+use crate::ios::ipa::extract_bundles;
 
 pub mod aasa;
+pub mod codesign;
 pub mod entitlements;
+pub mod handlers;
+pub mod ipa;
+pub mod provisioning;
 pub mod report;
 
 pub fn run(matches: &ArgMatches) {
@@ -17,9 +20,6 @@ pub fn run(matches: &ArgMatches) {
         panic!("URL must contain a host");
     }
 
-    if let Some(ipa) = matches.value_of("ipa") {
-        fs::metadata(ipa).expect("IPA file not found");
-    }
     let bundle_identifier = matches.value_of("bundle-identifier").unwrap();
 
     println!("Running checks for link: {}", url);
@@ -36,19 +36,18 @@ pub fn run(matches: &ArgMatches) {
         process::exit(-1);
     }
 
-    let mut ipa_res = None;
-    let mut entitlements = None;
-    if let Some(ipa) = matches.value_of("ipa") {
-        if let Some(entitlements_) = extract_info_from_ipa(ipa) {
-            let problems = entitlements_.get_problems(bundle_identifier, url.host().unwrap());
-            if !problems.is_empty() {
-                ipa_res = Some(problems)
-            }
-            entitlements = Some(entitlements_);
+    let mut bundles = None;
+    if let Some(app) = matches.value_of("app") {
+        match extract_bundles(app) {
+            Ok(bundles_) => bundles = Some(bundles_),
+            Err(e) => eprintln!("unable to read app: {}", e.to_string_human()),
         }
     }
 
     let aasa = aasa.ok().unwrap();
     let problems = aasa.get_problems();
-    report::report_problems_human(Some(problems), Some(aasa), ipa_res, entitlements);
+    report::report_problems_human(Some(problems), Some(aasa), None, None);
+    if let Some(bundles) = bundles {
+        report::report_bundles_human(&bundles, bundle_identifier, url.host().unwrap());
+    }
 }
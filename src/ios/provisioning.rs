@@ -0,0 +1,303 @@
+use crate::der;
+use crate::ios::entitlements::Entitlements;
+use plist::Plist;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::time::SystemTime;
+
+// embedded.mobileprovision: a plist signed by apple (CMS SignedData) that lists the entitlements
+// the app may use. entitlements of the binary that are not covered by the profile are dropped by iOS.
+
+const ASSOCIATED_DOMAINS: &str = "com.apple.developer.associated-domains";
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OCTET_STRING_CONSTRUCTED: u8 = 0x24;
+
+#[derive(Debug, PartialEq)]
+pub enum ProfileType {
+    // signed with a development certificate, get-task-allow is set
+    Development,
+    AdHoc,
+    Enterprise,
+    AppStore,
+}
+
+impl ProfileType {
+    pub fn to_string_human(&self) -> &'static str {
+        match self {
+            ProfileType::Development => "development",
+            ProfileType::AdHoc => "ad hoc distribution",
+            ProfileType::Enterprise => "enterprise distribution",
+            ProfileType::AppStore => "App Store distribution",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ProvisioningProfile {
+    pub name: Option<String>,
+    pub team_id: Option<String>,
+    // the application-identifier the profile was created for, may end in a wildcard
+    pub app_id: Option<String>,
+    pub expiration_date: Option<SystemTime>,
+    pub profile_type: ProfileType,
+    pub entitlements: BTreeMap<String, Plist>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidSignature,
+    InvalidPlist,
+}
+
+impl Error {
+    pub fn to_string_human(&self) -> String {
+        match self {
+            Error::InvalidSignature => "The provisioning profile is not a valid CMS signed file.".to_string(),
+            Error::InvalidPlist => "The content of the provisioning profile is not a valid plist.".to_string(),
+        }
+    }
+}
+
+impl From<der::Error> for Error {
+    fn from(_: der::Error) -> Self {
+        Error::InvalidSignature
+    }
+}
+
+#[derive(Debug)]
+pub enum Problem {
+    // days since the expiration
+    Expired(u64),
+    // the profile has no associated domains entitlement, the capability is not enabled for the app id
+    MissingAssociatedDomains,
+    // domain of the binary entitlements that the profile does not allow
+    DomainNotInProfile(String),
+    // application-identifier of the binary and of the profile
+    AppIdMismatch(String, String),
+    // team of the binary and of the profile
+    TeamMismatch(String, String),
+}
+
+impl Problem {
+    pub fn to_string_human(&self) -> String {
+        match self {
+            Problem::Expired(days) => format!("The provisioning profile expired {} days ago.", days),
+            Problem::MissingAssociatedDomains => "The provisioning profile does not include the Associated Domains capability, iOS ignores the associated domains of the app.".to_string(),
+            Problem::DomainNotInProfile(domain) => {
+                format!("The associated domain '{}' is not allowed by the provisioning profile.", domain)
+            }
+            Problem::AppIdMismatch(binary, profile) => format!(
+                "The application identifier '{}' of the app is not covered by the provisioning profile's '{}'.",
+                binary, profile
+            ),
+            Problem::TeamMismatch(binary, profile) => format!(
+                "The app is signed for team '{}' but the provisioning profile belongs to team '{}'.",
+                binary, profile
+            ),
+        }
+    }
+}
+
+// the signed content of a CMS SignedData structure (ContentInfo -> SignedData -> EncapsulatedContentInfo)
+fn content_from_cms(buf: &[u8]) -> Result<Vec<u8>, der::Error> {
+    let (content_info, _) = der::parse(buf)?;
+    let content_info = content_info.children()?;
+    let signed_data = content_info
+        .iter()
+        .find(|c| c.tag == der::TAG_CONTEXT_0)
+        .ok_or(der::Error::Truncated)?
+        .children()?;
+    let signed_data = signed_data
+        .iter()
+        .find(|c| c.tag == der::TAG_SEQUENCE)
+        .ok_or(der::Error::Truncated)?
+        .children()?;
+    // version, digest algorithms, then the encapsulated content
+    let encap_content_info = signed_data
+        .iter()
+        .find(|c| c.tag == der::TAG_SEQUENCE)
+        .ok_or(der::Error::Truncated)?
+        .children()?;
+    let content = encap_content_info
+        .iter()
+        .find(|c| c.tag == der::TAG_CONTEXT_0)
+        .ok_or(der::Error::Truncated)?
+        .children()?;
+
+    let mut res = Vec::new();
+    for octets in content {
+        match octets.tag {
+            TAG_OCTET_STRING => res.extend_from_slice(octets.content),
+            // BER splits long contents into chunks
+            TAG_OCTET_STRING_CONSTRUCTED => {
+                for chunk in octets.children()? {
+                    res.extend_from_slice(chunk.content);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(res)
+}
+
+#[test]
+fn test_content_from_cms() {
+    use crate::der::build_tlv as tlv;
+    let oid_data = tlv(0x06, &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x01]);
+    let mut chunks = tlv(TAG_OCTET_STRING, b"<plist>");
+    chunks.extend(tlv(TAG_OCTET_STRING, b"</plist>"));
+    let mut encap = oid_data.clone();
+    encap.extend(tlv(der::TAG_CONTEXT_0, &tlv(TAG_OCTET_STRING_CONSTRUCTED, &chunks)));
+
+    let mut signed_data = tlv(0x02, &[1]);
+    signed_data.extend(tlv(0x31, &[]));
+    signed_data.extend(tlv(der::TAG_SEQUENCE, &encap));
+    signed_data.extend(tlv(0x31, &[]));
+    let mut content_info = tlv(0x06, &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02]);
+    content_info.extend(tlv(der::TAG_CONTEXT_0, &tlv(der::TAG_SEQUENCE, &signed_data)));
+    let buf = tlv(der::TAG_SEQUENCE, &content_info);
+
+    assert_eq!(b"<plist></plist>".to_vec(), content_from_cms(&buf).unwrap());
+    assert!(content_from_cms(&buf[..buf.len() - 1]).is_err());
+}
+
+pub fn parse(buf: &[u8]) -> Result<ProvisioningProfile, Error> {
+    let content = content_from_cms(buf)?;
+    let plist = match Plist::read(Cursor::new(content)) {
+        Ok(Plist::Dictionary(plist)) => plist,
+        _ => return Err(Error::InvalidPlist),
+    };
+
+    let entitlements = match plist.get("Entitlements") {
+        Some(Plist::Dictionary(entitlements)) => entitlements.clone(),
+        _ => BTreeMap::new(),
+    };
+    let team_id = match plist.get("TeamIdentifier") {
+        Some(Plist::Array(teams)) => teams.iter().filter_map(|t| t.as_string()).next().map(|t| t.to_string()),
+        _ => None,
+    };
+    let app_id = match entitlements.get("application-identifier") {
+        Some(Plist::String(app_id)) => Some(app_id.clone()),
+        _ => None,
+    };
+    let expiration_date = match plist.get("ExpirationDate") {
+        Some(Plist::Date(date)) => Some(date.clone().into()),
+        _ => None,
+    };
+
+    let get_task_allow = entitlements.get("get-task-allow").and_then(|g| g.as_boolean()).unwrap_or(false);
+    let profile_type = if get_task_allow {
+        ProfileType::Development
+    } else if plist.get("ProvisionsAllDevices").and_then(|p| p.as_boolean()).unwrap_or(false) {
+        ProfileType::Enterprise
+    } else if plist.contains_key("ProvisionedDevices") {
+        ProfileType::AdHoc
+    } else {
+        ProfileType::AppStore
+    };
+
+    Ok(ProvisioningProfile {
+        name: plist.get("Name").and_then(|n| n.as_string()).map(|n| n.to_string()),
+        team_id,
+        app_id,
+        expiration_date,
+        profile_type,
+        entitlements,
+    })
+}
+
+// wildcard app ids (TEAM.com.example.*) cover every id with that prefix
+fn app_id_matches(profile: &str, binary: &str) -> bool {
+    if profile.ends_with('*') {
+        binary.starts_with(&profile[..profile.len() - 1])
+    } else {
+        profile == binary
+    }
+}
+
+impl ProvisioningProfile {
+    pub fn get_problems(&self, entitlements: &Entitlements, now: SystemTime) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        if let Some(expiration_date) = self.expiration_date {
+            if let Ok(expired_since) = now.duration_since(expiration_date) {
+                problems.push(Problem::Expired(expired_since.as_secs() / 86400));
+            }
+        }
+
+        match self.entitlements.get(ASSOCIATED_DOMAINS) {
+            None if !entitlements.associated_domains.is_empty() => problems.push(Problem::MissingAssociatedDomains),
+            // development and distribution profiles both list "*" which allows any domain
+            Some(Plist::String(ref wildcard)) if wildcard == "*" => {}
+            Some(Plist::Array(allowed)) => {
+                for domain in &entitlements.associated_domains {
+                    if !allowed.iter().any(|a| a.as_string() == Some(domain)) {
+                        problems.push(Problem::DomainNotInProfile(domain.clone()));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if let (Some(binary), Some(profile)) = (&entitlements.application_identifier, &self.app_id) {
+            if !app_id_matches(profile, binary) {
+                problems.push(Problem::AppIdMismatch(binary.clone(), profile.clone()));
+            }
+        }
+        if let (Some(binary), Some(profile)) = (&entitlements.team_identifier, &self.team_id) {
+            if binary != profile {
+                problems.push(Problem::TeamMismatch(binary.clone(), profile.clone()));
+            }
+        }
+        problems
+    }
+}
+
+#[test]
+fn test_get_problems() {
+    let mut profile_entitlements = BTreeMap::new();
+    profile_entitlements.insert(
+        ASSOCIATED_DOMAINS.to_string(),
+        Plist::Array(vec![Plist::String("applinks:example.com".to_string())]),
+    );
+    let now = SystemTime::now();
+    let mut profile = ProvisioningProfile {
+        name: None,
+        team_id: Some("TEAM".to_string()),
+        app_id: Some("TEAM.com.example.*".to_string()),
+        expiration_date: Some(now - std::time::Duration::from_secs(3 * 86400 + 10)),
+        profile_type: ProfileType::AppStore,
+        entitlements: profile_entitlements,
+    };
+    let mut entitlements = Entitlements::new();
+    entitlements.application_identifier = Some("TEAM.com.example.app".to_string());
+    entitlements.team_identifier = Some("OTHER".to_string());
+    entitlements.associated_domains = vec!["applinks:example.com".to_string(), "applinks:example.org".to_string()];
+
+    let problems: Vec<String> = profile.get_problems(&entitlements, now).iter().map(|p| format!("{:?}", p)).collect();
+    assert_eq!(
+        vec![
+            "Expired(3)",
+            "DomainNotInProfile(\"applinks:example.org\")",
+            "TeamMismatch(\"OTHER\", \"TEAM\")"
+        ],
+        problems
+    );
+
+    profile.entitlements.clear();
+    profile.expiration_date = None;
+    profile.app_id = Some("TEAM.com.example.other".to_string());
+    entitlements.team_identifier = None;
+    let problems: Vec<String> = profile.get_problems(&entitlements, now).iter().map(|p| format!("{:?}", p)).collect();
+    assert_eq!(
+        vec![
+            "MissingAssociatedDomains",
+            "AppIdMismatch(\"TEAM.com.example.app\", \"TEAM.com.example.other\")"
+        ],
+        problems
+    );
+
+    // the wildcard of real profiles allows every domain
+    profile.app_id = None;
+    profile.entitlements.insert(ASSOCIATED_DOMAINS.to_string(), Plist::String("*".to_string()));
+    assert!(profile.get_problems(&entitlements, now).is_empty());
+}
@@ -1,5 +1,9 @@
 use crate::ios::aasa;
 use crate::ios::entitlements::{self, Entitlements};
+use crate::ios::handlers::{self, Handler};
+use crate::ios::ipa::Bundle;
+use crate::ios::provisioning::ProvisioningProfile;
+use std::time::SystemTime;
 
 pub fn report_problems_human(
     aasa_problems: Option<Vec<aasa::Problem>>,
@@ -19,3 +23,72 @@ pub fn report_problems_human(
         }
     }
 }
+
+// the associated domains and problems of every bundle in the IPA. only the main app has to handle the link,
+// for extensions, App Clips and watch apps the signature is checked.
+pub fn report_bundles_human(bundles: &[Bundle], bundle_identifier: &str, domain: &str) {
+    for bundle in bundles {
+        println!("{}:", bundle.path);
+        for problem in &bundle.problems {
+            println!("  {}", problem.to_string_human());
+        }
+        if bundle.is_main() {
+            if let Some(problem) = bundle.check_identifier(bundle_identifier) {
+                println!("  {}", problem.to_string_human());
+            }
+        }
+        match bundle.entitlements {
+            Some(ref entitlements) => {
+                if entitlements.associated_domains.is_empty() {
+                    println!("  no associated domains");
+                } else {
+                    println!("  associated domains: {}", entitlements.associated_domains.join(", "));
+                }
+                let problems = if bundle.is_main() {
+                    entitlements.get_problems(bundle_identifier, domain)
+                } else {
+                    entitlements.get_signature_problems()
+                };
+                for problem in problems {
+                    println!("  {}", problem.to_string_human());
+                }
+                match bundle.handlers {
+                    Some(ref handlers) if bundle.is_main() => report_handlers_human(handlers),
+                    _ => {}
+                }
+                if let Some(ref profile) = bundle.profile {
+                    report_profile_human(profile, entitlements);
+                }
+            }
+            None if bundle.problems.is_empty() => {
+                println!("  no entitlements");
+                if bundle.is_main() {
+                    println!("  {}", entitlements::Problem::MissingAssociatedDomains.to_string_human());
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+fn report_handlers_human(handlers: &[Handler]) {
+    if handlers.is_empty() {
+        println!("  {}", handlers::Problem::NoHandler.to_string_human());
+    } else {
+        let names: Vec<&str> = handlers.iter().map(|h| h.to_string_human()).collect();
+        println!("  link handlers: {}", names.join(", "));
+    }
+}
+
+fn report_profile_human(profile: &ProvisioningProfile, entitlements: &Entitlements) {
+    println!(
+        "  provisioning profile '{}': {}, team {}, app id {}",
+        profile.name.as_ref().map(|n| &n[..]).unwrap_or(""),
+        profile.profile_type.to_string_human(),
+        profile.team_id.as_ref().map(|t| &t[..]).unwrap_or("unknown"),
+        profile.app_id.as_ref().map(|a| &a[..]).unwrap_or("unknown")
+    );
+    for problem in profile.get_problems(entitlements, SystemTime::now()) {
+        println!("  {}", problem.to_string_human());
+    }
+}
@@ -5,6 +5,7 @@ extern crate regex;
 extern crate regex_syntax;
 extern crate serde;
 extern crate serde_json;
+extern crate xml;
 extern crate zip;
 
 #[macro_use]
@@ -13,7 +14,6 @@ extern crate serde_derive;
 use self::ios::aasa;
 use self::ios::report;
 use crate::ios::aasa::fetch_and_check_sync;
-use crate::ios::entitlements::extract_info_from_ipa;
 use crate::android::assetlinks;
 use clap::{App, Arg, ArgMatches, SubCommand};
 use http::Uri;
@@ -28,10 +28,11 @@ fn main() {
             SubCommand::with_name("ios")
                 .about("iOS related checks")
                 .arg(
-                    Arg::with_name("ipa")
-                        .long("ipa")
+                    Arg::with_name("app")
+                        .long("app")
+                        .alias("ipa")
                         .value_name("FILE")
-                        .help("IPA to check against")
+                        .help("IPA, .app, .xcarchive, executable or .entitlements file to check against")
                         .takes_value(true),
                 )
                 .arg(
@@ -56,9 +57,57 @@ fn main() {
                     Arg::with_name("apk")
                         .long("apk")
                         .value_name("FILE")
-                        .help("APK to check against")
+                        .help("APK, App Bundle (.aab), APK set (.apks) or directory of split APKs to check against")
                         .takes_value(true),
                 )
+                .arg(
+                    Arg::with_name("manifest")
+                        .long("manifest")
+                        .value_name("FILE")
+                        .help("Source or merged AndroidManifest.xml to check against")
+                        .conflicts_with("apk")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("placeholder")
+                        .long("placeholder")
+                        .value_name("NAME=VALUE")
+                        .help("Value of a ${NAME} placeholder in the manifest")
+                        .requires("manifest")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("res")
+                        .long("res")
+                        .value_name("DIR")
+                        .help("Resource directory with the string resources of the manifest (default: res next to the manifest)")
+                        .requires("manifest")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("data-combinations")
+                        .long("data-combinations")
+                        .help("List every scheme, host and path combination the intent filters accept"),
+                )
+                .arg(
+                    Arg::with_name("verification-plan")
+                        .long("verification-plan")
+                        .help("List the hosts Android verifies on install and the assetlinks.json fetched for each"),
+                )
+                .arg(
+                    Arg::with_name("login-creds")
+                        .long("login-creds")
+                        .help("Check that credentials are shared between the app and the site (get_login_creds)"),
+                )
+                .arg(
+                    Arg::with_name("asset-statements")
+                        .long("asset-statements")
+                        .help("Check that the sites in the asset_statements of the app confirm the statements"),
+                )
                 .arg(
                     Arg::with_name("app-id")
                         .value_name("APP_ID")
@@ -74,14 +123,64 @@ fn main() {
                         .index(2),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("android-links")
+                .about("Lists every deep link an Android app handles")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Output format")
+                        .possible_values(&["human", "json", "csv"])
+                        .default_value("human")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("apk")
+                        .value_name("FILE")
+                        .help("APK, App Bundle (.aab), APK set (.apks) or directory of split APKs")
+                        .required_unless("manifest")
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("manifest")
+                        .long("manifest")
+                        .value_name("FILE")
+                        .help("Source or merged AndroidManifest.xml instead of an APK")
+                        .conflicts_with("apk")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("placeholder")
+                        .long("placeholder")
+                        .value_name("NAME=VALUE")
+                        .help("Value of a ${NAME} placeholder in the manifest")
+                        .requires("manifest")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("res")
+                        .long("res")
+                        .value_name("DIR")
+                        .help("Resource directory with the string resources of the manifest (default: res next to the manifest)")
+                        .requires("manifest")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .takes_value(true),
+                ),
+        )
         .get_matches();
 
     match matches.subcommand() {
         ("ios", Some(m)) => ios::run(m),
         ("android", Some(m)) => android::run(m),
+        ("android-links", Some(m)) => android::list_deep_links(m),
         _ => unimplemented!(),
     }
 }
 
 mod ios;
 mod android;
+mod der;